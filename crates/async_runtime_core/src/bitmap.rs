@@ -0,0 +1,87 @@
+pub struct BitMap64 {
+    data: u64,
+}
+
+impl Default for BitMap64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitMap64 {
+    #[inline]
+    pub const fn new() -> Self {
+        BitMap64 { data: 0 }
+    }
+
+    #[inline]
+    pub fn set(&mut self, pos: usize) {
+        assert!(pos < 64, "Position out of range");
+        self.data |= 1 << pos;
+    }
+
+    #[inline]
+    pub fn full(&self) -> bool {
+        self.find_first_zero() == 64
+    }
+
+    #[inline]
+    pub fn emtpy(&self) -> bool {
+        self.find_first_one() == 64
+    }
+
+    #[inline]
+    pub fn clear(&mut self, pos: usize) {
+        assert!(pos < 64, "Position out of range");
+        self.data &= !(1 << pos);
+    }
+
+    #[inline]
+    pub fn find_first_one(&self) -> usize {
+        self.data.trailing_zeros() as usize
+    }
+
+    #[inline]
+    pub fn find_first_zero(&self) -> usize {
+        self.data.trailing_ones() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitMap64;
+
+    #[test]
+    fn set_and_clear() {
+        let mut bitmap = BitMap64::new();
+        assert!(bitmap.emtpy());
+        assert_eq!(bitmap.find_first_zero(), 0);
+        bitmap.set(0);
+        bitmap.set(5);
+        assert_eq!(bitmap.find_first_one(), 0);
+        assert_eq!(bitmap.find_first_zero(), 1);
+        bitmap.clear(0);
+        assert_eq!(bitmap.find_first_one(), 5);
+        bitmap.clear(5);
+        assert!(bitmap.emtpy());
+    }
+
+    #[test]
+    fn full() {
+        let mut bitmap = BitMap64::new();
+        for pos in 0..64 {
+            assert!(!bitmap.full());
+            bitmap.set(pos);
+        }
+        assert!(bitmap.full());
+        assert_eq!(bitmap.find_first_zero(), 64);
+        bitmap.clear(63);
+        assert_eq!(bitmap.find_first_zero(), 63);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_range() {
+        BitMap64::new().set(64);
+    }
+}
@@ -0,0 +1,219 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::collections::VecDeque;
+use alloc::task::Wake;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+#[derive(Default, Eq, PartialEq, Debug, Clone, Copy, Hash, Ord, PartialOrd)]
+#[repr(transparent)]
+pub struct CoroutineId(pub u32);
+
+// 协程 Id 的低 COROUTINE_INDEX_BITS 位为编号，高位为代数
+// 协程结束后编号被回收，再次分配时代数加一，保存在tcb等处的旧 Id 不会匹配到复用该编号的新协程
+// Id 同时出现在共享内存的 IPCItem 中，不能加宽，代数在同一编号被复用 2^12 次后回绕。
+// 回收的编号按 FIFO 复用，且至少积累 COROUTINE_REUSE_MIN 个后才复用，
+// 同一编号的代数回绕至少需要 2^12 * COROUTINE_REUSE_MIN 次分配
+const COROUTINE_INDEX_BITS: u32 = 20;
+const COROUTINE_INDEX_MASK: u32 = (1 << COROUTINE_INDEX_BITS) - 1;
+const COROUTINE_REUSE_MIN: usize = 1024;
+
+struct CoroutineIdAllocator {
+    // 从未分配过的最小编号
+    next: u32,
+    // 已回收的 Id（保留回收时的代数），先回收的先复用
+    free: VecDeque<CoroutineId>,
+    // 回收的 Id 达到该数量后才复用，编号空间用尽时不受限制
+    reuse_min: usize,
+}
+
+impl CoroutineIdAllocator {
+    const fn new(reuse_min: usize) -> Self {
+        Self { next: 0, free: VecDeque::new(), reuse_min }
+    }
+
+    fn generate(&mut self) -> CoroutineId {
+        if self.free.len() >= self.reuse_min || self.next > COROUTINE_INDEX_MASK {
+            if let Some(old) = self.free.pop_front() {
+                return CoroutineId(old.0.wrapping_add(1 << COROUTINE_INDEX_BITS));
+            }
+        }
+        let index = self.next;
+        if index > COROUTINE_INDEX_MASK {
+            // 同时存在的协程数超过编号空间
+            panic!("too many live coroutines!")
+        }
+        self.next += 1;
+        CoroutineId(index)
+    }
+
+    fn release(&mut self, cid: CoroutineId) {
+        self.free.push_back(cid);
+    }
+}
+
+static COROUTINE_ID_ALLOCATOR: Mutex<CoroutineIdAllocator> = Mutex::new(CoroutineIdAllocator::new(COROUTINE_REUSE_MIN));
+
+impl CoroutineId {
+    /// 生成新的协程 Id，回收的编号足够多时按回收顺序复用
+    pub fn generate() -> CoroutineId {
+        COROUTINE_ID_ALLOCATOR.lock().generate()
+    }
+    /// 回收协程 Id，只能在协程从执行器中移除后调用一次
+    pub fn release(self) {
+        COROUTINE_ID_ALLOCATOR.lock().release(self);
+    }
+    /// 协程 Id 的编号部分
+    pub fn index(&self) -> u32 {
+        self.0 & COROUTINE_INDEX_MASK
+    }
+    /// 协程 Id 的代数部分
+    pub fn generation(&self) -> u32 {
+        self.0 >> COROUTINE_INDEX_BITS
+    }
+    /// 根据 usize 生成协程 Id
+    pub fn from_val(v: u32) -> Self {
+        Self(v)
+    }
+    /// 获取协程 Id 的 usize
+    pub fn get_val(&self) -> u32 {
+        self.0
+    }
+}
+
+struct CoroutineWaker {
+    cid: CoroutineId,
+    wake: fn(&CoroutineId),
+}
+
+impl CoroutineWaker {
+    /// 新建协程 waker，唤醒时调用 wake
+    pub fn waker(cid: CoroutineId, wake: fn(&CoroutineId)) -> Waker {
+        Waker::from(Arc::new(Self { cid, wake }))
+    }
+}
+
+impl Wake for CoroutineWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    /// 将协程重新放回执行器的就绪队列
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.wake)(&self.cid);
+    }
+}
+
+pub type CoroutineFuture = Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>;
+
+pub struct Coroutine{
+    /// 协程编号
+    pub cid: CoroutineId,
+    /// 所属线程的tcb指针，内核自身的协程为0
+    pub owner: usize,
+    /// future
+    pub inner: RefCell<CoroutineInner>,
+}
+
+pub struct CoroutineInner {
+    pub future: CoroutineFuture,
+    /// waker
+    pub waker: Arc<Waker>,
+}
+
+impl Coroutine {
+    /// 生成协程，wake 为其 waker 被唤醒时的回调
+    // 协程只在执行器的锁保护下访问，不需要 Sync
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new(future: CoroutineFuture, owner: usize, wake: fn(&CoroutineId)) -> Arc<Self> {
+        let cid = CoroutineId::generate();
+        Arc::new(
+            Coroutine {
+                cid,
+                owner,
+                inner: RefCell::new(
+                    CoroutineInner {
+                        future,
+                        waker: Arc::new(CoroutineWaker::waker(cid, wake)),
+                    }
+                )
+
+            }
+        )
+    }
+    /// 执行
+    pub fn execute(self: Arc<Self>) -> Poll<()> {
+        let waker = self.inner.borrow().waker.clone();
+        let mut context = Context::from_waker(&waker);
+
+        self.inner.borrow_mut().future.as_mut().poll(&mut context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_bumps_generation() {
+        let mut allocator = CoroutineIdAllocator::new(1);
+        let first = allocator.generate();
+        let second = allocator.generate();
+        assert_eq!((first.index(), first.generation()), (0, 0));
+        assert_eq!((second.index(), second.generation()), (1, 0));
+        allocator.release(first);
+        let reused = allocator.generate();
+        assert_eq!((reused.index(), reused.generation()), (0, 1));
+        assert_ne!(reused, first);
+        assert_eq!(allocator.generate().index(), 2);
+    }
+
+    #[test]
+    fn reuse_waits_for_min_and_is_fifo() {
+        let mut allocator = CoroutineIdAllocator::new(2);
+        let first = allocator.generate();
+        let second = allocator.generate();
+        allocator.release(first);
+        // 回收的 Id 不足 reuse_min 个时分配新编号
+        let third = allocator.generate();
+        assert_eq!(third.index(), 2);
+        allocator.release(second);
+        allocator.release(third);
+        assert_eq!(allocator.generate().index(), first.index());
+        allocator.release(first);
+        assert_eq!(allocator.generate().index(), second.index());
+    }
+
+    #[test]
+    fn generation_wraps_after_reuse_min_cycles() {
+        let mut allocator = CoroutineIdAllocator::new(COROUTINE_REUSE_MIN);
+        let first = allocator.generate();
+        let mut cid = first;
+        let mut cycles = 0;
+        // 每次只有一个存活协程时，编号 0 每 COROUTINE_REUSE_MIN 次分配才被复用一次
+        loop {
+            allocator.release(cid);
+            cid = allocator.generate();
+            cycles += 1;
+            if cid == first {
+                break;
+            }
+        }
+        assert_eq!(cycles, (1 << (32 - COROUTINE_INDEX_BITS)) * COROUTINE_REUSE_MIN);
+    }
+
+    #[test]
+    fn waker_calls_hook() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        static WOKEN: AtomicU32 = AtomicU32::new(u32::MAX);
+        fn wake(cid: &CoroutineId) {
+            WOKEN.store(cid.0, Ordering::SeqCst);
+        }
+        let waker = CoroutineWaker::waker(CoroutineId(42), wake);
+        waker.wake_by_ref();
+        assert_eq!(WOKEN.load(Ordering::SeqCst), 42);
+    }
+}
@@ -0,0 +1,502 @@
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::task::Poll;
+use spin::Mutex;
+use crate::coroutine::{Coroutine, CoroutineFuture, CoroutineId};
+
+// 执行器依赖的内核功能
+#[derive(Clone, Copy)]
+pub struct ExecutorHooks {
+    // 就绪队列的排序键（越小越先执行），参数为协程所属线程
+    pub ready_key: fn(usize) -> (usize, usize),
+    // 当前时间，用于统计
+    pub now: fn() -> usize,
+    // 协程的waker被唤醒时调用
+    pub wake: fn(&CoroutineId),
+}
+
+// 执行器开始执行协程的原因，以及处理协程被唤醒的来源
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AsyncWakeSource {
+    // 用户态通过SysWakeSyscallHandler唤醒
+    Syscall = 0,
+    // 时钟中断
+    Timer,
+    // 其他核发送的ipi
+    Ipi,
+    // 轮询核
+    Polling,
+}
+
+pub const ASYNC_WAKE_SOURCE_NUM: usize = 4;
+
+// 每个执行器的统计
+#[derive(Clone, Copy, Default)]
+pub struct ExecutorStats {
+    // 生成的协程数（不包括从其他核迁移来的协程）
+    pub spawned: usize,
+    // 执行结束的协程数
+    pub completed: usize,
+    // 被其他核窃取的协程数
+    pub stolen: usize,
+    // poll的次数
+    pub polls: usize,
+    // poll协程的总时间
+    pub run_cycles: usize,
+    // 就绪队列的最大长度
+    pub ready_max: usize,
+    // 按原因统计的开始执行协程的次数
+    pub runs: [usize; ASYNC_WAKE_SOURCE_NUM],
+}
+
+impl ExecutorStats {
+    pub const fn new() -> Self {
+        Self { spawned: 0, completed: 0, stolen: 0, polls: 0, run_cycles: 0, ready_max: 0, runs: [0; ASYNC_WAKE_SOURCE_NUM] }
+    }
+}
+
+// 就绪协程按ready_key排序，同一键先进先出
+pub struct ReadyQueue {
+    queues: BTreeMap<(usize, usize), VecDeque<CoroutineId>>,
+    key: fn(usize) -> (usize, usize),
+}
+
+impl ReadyQueue {
+    pub const fn new(key: fn(usize) -> (usize, usize)) -> Self {
+        Self { queues: BTreeMap::new(), key }
+    }
+
+    pub fn push_back(&mut self, task: &Coroutine) {
+        self.queues.entry((self.key)(task.owner)).or_default().push_back(task.cid);
+    }
+
+    pub fn pop_front(&mut self) -> Option<CoroutineId> {
+        let mut entry = self.queues.first_entry()?;
+        let cid = entry.get_mut().pop_front();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        cid
+    }
+
+    // 从优先级最低的队列尾部取出，用于窃取
+    pub fn pop_back(&mut self) -> Option<CoroutineId> {
+        let mut entry = self.queues.last_entry()?;
+        let cid = entry.get_mut().pop_back();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        cid
+    }
+
+    pub fn remove(&mut self, cid: &CoroutineId) {
+        self.queues.retain(|_, queue| {
+            queue.retain(|c| c != cid);
+            !queue.is_empty()
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.values().map(|queue| queue.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+}
+
+pub struct ExecutorInner {
+    pub current: Option<CoroutineId>,
+    pub tasks: BTreeMap<CoroutineId, Arc<Coroutine>>,
+    pub immediate_value: BTreeMap<CoroutineId, u64>,
+    pub ready_queue: ReadyQueue,
+    pub pending_set: BTreeSet<CoroutineId>,
+    // 在poll过程中被唤醒的协程，poll返回Pending后直接放回就绪队列
+    pub notified_set: BTreeSet<CoroutineId>,
+    // 本次开始执行协程的原因
+    pub source: AsyncWakeSource,
+    pub stats: ExecutorStats,
+}
+
+// 迁移中的协程（被其他核窃取或被唤醒到指定核）
+pub struct MigratedCoroutine {
+    task: Arc<Coroutine>,
+    value: Option<u64>,
+}
+
+// 每个核一个执行器，其他核的唤醒和窃取通过自旋锁与本核的执行互斥，
+// 协程被poll时不持有锁
+pub struct Executor {
+    inner: Mutex<ExecutorInner>,
+    hooks: ExecutorHooks,
+}
+
+impl Executor {
+    pub const fn new(hooks: ExecutorHooks) -> Self {
+        Self {
+            inner: Mutex::new(ExecutorInner {
+                current: None,
+                tasks: BTreeMap::new(),
+                immediate_value: BTreeMap::new(),
+                ready_queue: ReadyQueue::new(hooks.ready_key),
+                pending_set: BTreeSet::new(),
+                notified_set: BTreeSet::new(),
+                source: AsyncWakeSource::Timer,
+                stats: ExecutorStats::new(),
+            }),
+            hooks,
+        }
+    }
+
+    pub fn spawn(&self, future: CoroutineFuture, owner: usize) -> CoroutineId {
+        let task = Coroutine::new(future, owner, self.hooks.wake);
+        let cid = task.cid;
+        let mut inner = self.inner.lock();
+        inner.ready_queue.push_back(&task);
+        inner.tasks.insert(cid, task);
+        inner.stats.spawned += 1;
+        cid
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().tasks.is_empty()
+    }
+
+    #[inline]
+    pub fn current(&self) -> Option<CoroutineId> {
+        self.inner.lock().current
+    }
+
+    pub fn fetch(&self) -> Option<Arc<Coroutine>> {
+        let mut inner = self.inner.lock();
+        let ready = inner.ready_queue.len();
+        if ready > inner.stats.ready_max {
+            inner.stats.ready_max = ready;
+        }
+        if let Some(cid) = inner.ready_queue.pop_front() {
+            let task = inner.tasks.get(&cid).unwrap().clone();
+            inner.current = Some(cid);
+            Some(task)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn is_pending(&self, cid: CoroutineId) -> bool {
+        self.inner.lock().pending_set.contains(&cid)
+    }
+
+    // 协程不属于本执行器时返回false
+    pub fn wake(&self, cid: &CoroutineId, value: Option<u64>) -> bool {
+        let mut inner = self.inner.lock();
+        if !inner.tasks.contains_key(cid) {
+            return false;
+        }
+        if let Some(value) = value {
+            inner.immediate_value.insert(*cid, value);
+        }
+        // 只有挂起的协程才放回就绪队列，重复唤醒被忽略
+        if inner.pending_set.remove(cid) {
+            let task = inner.tasks.get(cid).unwrap().clone();
+            inner.ready_queue.push_back(&task);
+        } else if inner.current == Some(*cid) {
+            inner.notified_set.insert(*cid);
+        }
+        true
+    }
+
+    #[inline]
+    pub fn take_immediate_value(&self, cid: &CoroutineId) -> Option<u64> {
+        self.inner.lock().immediate_value.remove(cid)
+    }
+
+    // 取出一个挂起的协程，用于将其唤醒到其他核上
+    pub fn take_pending(&self, cid: &CoroutineId) -> Option<MigratedCoroutine> {
+        let mut inner = self.inner.lock();
+        if !inner.pending_set.remove(cid) {
+            return None;
+        }
+        let task = inner.tasks.remove(cid).unwrap();
+        let value = inner.immediate_value.remove(cid);
+        Some(MigratedCoroutine { task, value })
+    }
+
+    // 窃取就绪队列尾部的一半协程，正在执行和挂起的协程不会被窃取
+    pub fn steal(&self) -> Vec<MigratedCoroutine> {
+        let mut inner = self.inner.lock();
+        let ready = inner.ready_queue.len();
+        let count = ready - ready / 2;
+        let mut stolen = Vec::with_capacity(count);
+        for _ in 0..count {
+            let cid = inner.ready_queue.pop_back().unwrap();
+            let task = inner.tasks.remove(&cid).unwrap();
+            let value = inner.immediate_value.remove(&cid);
+            stolen.push(MigratedCoroutine { task, value });
+        }
+        inner.stats.stolen += count;
+        stolen
+    }
+
+    // 取消属于某个线程的所有协程，正在执行的协程在本次poll返回后被丢弃
+    pub fn cancel_owned_by(&self, owner: usize) -> Vec<CoroutineId> {
+        let mut inner = self.inner.lock();
+        let cids: Vec<CoroutineId> = inner.tasks.values().filter(|task| task.owner == owner).map(|task| task.cid).collect();
+        for cid in cids.iter() {
+            Self::remove_task(&mut inner, cid);
+        }
+        cids
+    }
+
+    // 取消单个协程，协程不属于本执行器时返回false
+    pub fn cancel(&self, cid: &CoroutineId) -> bool {
+        let mut inner = self.inner.lock();
+        if !inner.tasks.contains_key(cid) {
+            return false;
+        }
+        Self::remove_task(&mut inner, cid);
+        true
+    }
+
+    // 移除协程并回收其 Id
+    fn remove_task(inner: &mut ExecutorInner, cid: &CoroutineId) {
+        if inner.tasks.remove(cid).is_some() {
+            cid.release();
+        }
+        inner.immediate_value.remove(cid);
+        inner.pending_set.remove(cid);
+        inner.notified_set.remove(cid);
+        inner.ready_queue.remove(cid);
+    }
+
+    pub fn push_ready(&self, coroutine: MigratedCoroutine) {
+        let mut inner = self.inner.lock();
+        let cid = coroutine.task.cid;
+        if let Some(value) = coroutine.value {
+            inner.immediate_value.insert(cid, value);
+        }
+        inner.ready_queue.push_back(&coroutine.task);
+        inner.tasks.insert(cid, coroutine.task);
+    }
+
+    // 记录开始执行协程的原因，在run_until_blocked之前调用
+    pub fn set_source(&self, source: AsyncWakeSource) {
+        let mut inner = self.inner.lock();
+        inner.source = source;
+        inner.stats.runs[source as usize] += 1;
+    }
+
+    #[inline]
+    pub fn source(&self) -> AsyncWakeSource {
+        self.inner.lock().source
+    }
+
+    // 读取统计，reset为true时读取后清零
+    pub fn stats(&self, reset: bool) -> ExecutorStats {
+        let mut inner = self.inner.lock();
+        let stats = inner.stats;
+        if reset {
+            inner.stats = ExecutorStats::new();
+        }
+        stats
+    }
+
+    #[inline]
+    pub fn run_until_complete(&self) {
+        while !self.is_empty() {
+            self.run_until_blocked();
+        }
+    }
+
+    pub fn run_until_blocked(&self) {
+        while let Some(task) = self.fetch() {
+            let cid = task.cid;
+            let start = (self.hooks.now)();
+            let poll = task.clone().execute();
+            let mut inner = self.inner.lock();
+            inner.current = None;
+            inner.stats.polls += 1;
+            inner.stats.run_cycles += (self.hooks.now)() - start;
+            if !inner.tasks.contains_key(&cid) {
+                // 协程在执行过程中被取消
+                inner.notified_set.remove(&cid);
+                continue;
+            }
+            match poll {
+                Poll::Ready(_) => {
+                    inner.stats.completed += 1;
+                    Self::remove_task(&mut inner, &cid);
+                }
+                Poll::Pending => {
+                    if inner.notified_set.remove(&cid) {
+                        inner.ready_queue.push_back(&task);
+                    } else {
+                        inner.pending_set.insert(cid);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::Context;
+    use std::cell::RefCell;
+
+    // 测试中owner直接作为优先级，越小越先执行
+    fn test_ready_key(owner: usize) -> (usize, usize) {
+        (0, owner)
+    }
+
+    fn test_now() -> usize {
+        0
+    }
+
+    fn test_wake(cid: &CoroutineId) {
+        EXECUTOR.with(|executor| executor.wake(cid, None));
+    }
+
+    const TEST_HOOKS: ExecutorHooks = ExecutorHooks { ready_key: test_ready_key, now: test_now, wake: test_wake };
+
+    thread_local! {
+        // waker只能通过函数指针找到执行器，每个测试线程一个
+        static EXECUTOR: &'static Executor = Box::leak(Box::new(Executor::new(TEST_HOOKS)));
+        static TRACE: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn executor() -> &'static Executor {
+        EXECUTOR.with(|executor| *executor)
+    }
+
+    fn trace(tag: usize) {
+        TRACE.with(|trace| trace.borrow_mut().push(tag));
+    }
+
+    fn take_trace() -> Vec<usize> {
+        TRACE.with(|trace| core::mem::take(&mut *trace.borrow_mut()))
+    }
+
+    // 第一次poll返回Pending，wake_self为true时在poll中唤醒自己
+    struct YieldOnce {
+        yielded: bool,
+        wake_self: bool,
+    }
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.yielded {
+                return Poll::Ready(());
+            }
+            self.yielded = true;
+            if self.wake_self {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+
+    fn spawn_traced(owner: usize, tag: usize, yields: usize, wake_self: bool) -> CoroutineId {
+        executor().spawn(Box::pin(async move {
+            trace(tag);
+            for _ in 0..yields {
+                YieldOnce { yielded: false, wake_self }.await;
+                trace(tag);
+            }
+        }), owner)
+    }
+
+    #[test]
+    fn ready_order_by_key_then_fifo() {
+        spawn_traced(3, 30, 0, false);
+        spawn_traced(1, 10, 0, false);
+        spawn_traced(3, 31, 0, false);
+        spawn_traced(1, 11, 0, false);
+        spawn_traced(2, 20, 0, false);
+        executor().run_until_blocked();
+        assert_eq!(take_trace(), [10, 11, 20, 30, 31]);
+        assert!(executor().is_empty());
+    }
+
+    #[test]
+    fn pending_until_woken() {
+        let first = spawn_traced(1, 1, 1, false);
+        let second = spawn_traced(1, 2, 1, false);
+        executor().run_until_blocked();
+        assert_eq!(take_trace(), [1, 2]);
+        assert!(executor().is_pending(first) && executor().is_pending(second));
+        // 按唤醒顺序执行，重复唤醒被忽略
+        assert!(executor().wake(&second, None));
+        assert!(executor().wake(&second, None));
+        assert!(executor().wake(&first, Some(7)));
+        assert_eq!(executor().take_immediate_value(&first), Some(7));
+        executor().run_until_blocked();
+        assert_eq!(take_trace(), [2, 1]);
+        assert!(executor().is_empty());
+        assert!(!executor().wake(&first, None));
+    }
+
+    #[test]
+    fn wake_during_poll_requeues() {
+        spawn_traced(1, 1, 2, true);
+        spawn_traced(1, 2, 0, false);
+        executor().run_until_blocked();
+        // 在poll中被唤醒的协程放回队尾，不会挂起
+        assert_eq!(take_trace(), [1, 2, 1, 1]);
+        assert!(executor().is_empty());
+    }
+
+    #[test]
+    fn cancel_removes_task() {
+        let first = spawn_traced(1, 1, 1, false);
+        let second = spawn_traced(1, 2, 0, false);
+        assert!(executor().cancel(&second));
+        assert!(!executor().cancel(&second));
+        executor().run_until_blocked();
+        assert_eq!(take_trace(), [1]);
+        assert_eq!(executor().cancel_owned_by(1), [first]);
+        assert!(executor().is_empty());
+    }
+
+    #[test]
+    fn steal_takes_lowest_priority_half() {
+        let owners = [1, 2, 3, 4, 5];
+        for owner in owners {
+            spawn_traced(owner, owner, 0, false);
+        }
+        let stolen = executor().steal();
+        let thief = Executor::new(TEST_HOOKS);
+        assert_eq!(stolen.len(), 3);
+        for coroutine in stolen {
+            thief.push_ready(coroutine);
+        }
+        executor().run_until_blocked();
+        assert_eq!(take_trace(), [1, 2]);
+        thief.run_until_blocked();
+        assert_eq!(take_trace(), [3, 4, 5]);
+        assert_eq!(executor().stats(false).stolen, 3);
+    }
+
+    #[test]
+    fn stats() {
+        executor().stats(true);
+        executor().set_source(AsyncWakeSource::Ipi);
+        spawn_traced(1, 1, 1, false);
+        spawn_traced(1, 2, 0, false);
+        executor().run_until_blocked();
+        take_trace();
+        let stats = executor().stats(true);
+        assert_eq!((stats.spawned, stats.completed, stats.polls, stats.ready_max), (2, 1, 2, 2));
+        assert_eq!(stats.runs[AsyncWakeSource::Ipi as usize], 1);
+        assert_eq!(executor().source(), AsyncWakeSource::Ipi);
+        assert_eq!(executor().stats(false).spawned, 0);
+        executor().cancel_owned_by(1);
+    }
+}
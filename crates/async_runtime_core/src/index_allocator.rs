@@ -0,0 +1,80 @@
+// 按位图分配编号，分配最小的空闲编号
+#[derive(Copy, Clone)]
+pub struct IndexAllocator<const SIZE: usize> where
+    [(); (SIZE + 7) / 8]: {
+    bitmap: [u8; (SIZE + 7) / 8]
+}
+
+impl<const SIZE: usize> Default for IndexAllocator<SIZE> where
+    [(); (SIZE + 7) / 8]: {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> IndexAllocator<SIZE> where
+    [(); (SIZE + 7) / 8]: {
+    pub fn new() -> Self {
+        Self {
+            bitmap :[0; (SIZE + 7) / 8]
+        }
+    }
+
+    pub fn allocate(&mut self) -> Option<usize> {
+        let index = (0..SIZE).find(|i| {self.bitmap[i / 8] & (1 << (i % 8)) == 0 })?;
+        self.bitmap[index / 8] |= 1 << (index % 8);
+        Some(index)
+    }
+
+    // 将编号标记为已分配，之后不会再被allocate返回
+    pub fn reserve(&mut self, index: usize) {
+        assert!(index < SIZE, "Index out of range");
+        self.bitmap[index / 8] |= 1 << (index % 8);
+    }
+
+    pub fn release(&mut self, index: usize) {
+        self.bitmap[index / 8] &= !(1 << (index % 8));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexAllocator;
+
+    #[test]
+    fn allocate_lowest_free() {
+        let mut allocator = IndexAllocator::<16>::new();
+        assert_eq!(allocator.allocate(), Some(0));
+        assert_eq!(allocator.allocate(), Some(1));
+        assert_eq!(allocator.allocate(), Some(2));
+        allocator.release(1);
+        assert_eq!(allocator.allocate(), Some(1));
+        assert_eq!(allocator.allocate(), Some(3));
+    }
+
+    #[test]
+    fn exhaustion() {
+        // SIZE不是8的倍数时，位图最后一个字节中多余的位不能被分配
+        let mut allocator = IndexAllocator::<10>::new();
+        for index in 0..10 {
+            assert_eq!(allocator.allocate(), Some(index));
+        }
+        assert_eq!(allocator.allocate(), None);
+        allocator.release(7);
+        assert_eq!(allocator.allocate(), Some(7));
+        assert_eq!(allocator.allocate(), None);
+    }
+
+    #[test]
+    fn reserved_indices_are_skipped() {
+        let mut allocator = IndexAllocator::<8>::new();
+        for index in [1, 2, 3, 4] {
+            allocator.reserve(index);
+        }
+        let allocated: Vec<usize> = core::iter::from_fn(|| allocator.allocate()).collect();
+        assert_eq!(allocated, [0, 5, 6, 7]);
+        allocator.release(6);
+        assert_eq!(allocator.allocate(), Some(6));
+        assert_eq!(allocator.allocate(), None);
+    }
+}
@@ -0,0 +1,19 @@
+#![cfg_attr(not(test), no_std)]
+#![allow(incomplete_features)]
+#![feature(generic_const_exprs)]
+// 内核固定的工具链中div_ceil还不能在常量表达式中使用
+#![allow(clippy::manual_div_ceil)]
+
+extern crate alloc;
+
+pub mod coroutine;
+pub mod executor;
+pub mod ring_buffer;
+pub mod bitmap;
+pub mod index_allocator;
+
+pub use coroutine::{Coroutine, CoroutineId};
+pub use executor::{Executor, ExecutorHooks, ExecutorStats, AsyncWakeSource, MigratedCoroutine, ASYNC_WAKE_SOURCE_NUM};
+pub use ring_buffer::{RingBuffer, SafeRingBuffer, CachePaddedIndex, MAX_ITEM_NUM};
+pub use bitmap::BitMap64;
+pub use index_allocator::IndexAllocator;
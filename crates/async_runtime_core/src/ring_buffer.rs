@@ -0,0 +1,237 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// 用户态与内核共享的请求/响应队列的容量
+pub const MAX_ITEM_NUM: usize = 4096;
+
+#[derive(Copy, Clone)]
+pub struct RingBuffer<T, const SIZE: usize> {
+    data: [T; SIZE],
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<T, const SIZE: usize> Default for RingBuffer<T, SIZE> where T: Default + Copy + Clone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const SIZE: usize> RingBuffer<T, SIZE> where T: Default + Copy + Clone {
+    pub fn new() -> Self {
+        Self {
+            data: [T::default(); SIZE],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        (self.end + SIZE - self.start) % SIZE
+    }
+
+    #[inline]
+    pub fn empty(&self) -> bool {
+        self.end == self.start
+    }
+
+    #[inline]
+    pub fn full(&self) -> bool {
+        (self.end + 1) % SIZE == self.start
+    }
+
+    #[inline]
+    #[allow(clippy::result_unit_err)]
+    pub fn push(&mut self, item: &T) -> Result<(), ()> {
+        if !self.full() {
+            self.data[self.end] = *item;
+            self.end = (self.end + 1) % SIZE;
+            return Ok(());
+        }
+        Err(())
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if !self.empty() {
+            let ans = self.data[self.start];
+            self.start = (self.start + 1) % SIZE;
+            Some(ans)
+        } else {
+            None
+        }
+    }
+}
+
+
+// 放在单独缓存行中的原子下标，避免生产者和消费者互相干扰
+#[repr(C, align(64))]
+pub struct CachePaddedIndex(pub AtomicUsize);
+
+/// 单生产者单消费者的共享内存环形队列，生产者和消费者可以在不同的核上（用户态线程和内核协程）
+///
+/// 内存布局（repr(C)，用户态库按此布局访问）：
+/// - 偏移 0: `head: usize`，消费者下一个读取的位置，只由消费者写
+/// - 偏移 64: `tail: usize`，生产者下一个写入的位置，只由生产者写
+/// - 偏移 128: `data: [T; SIZE]`，位置 i 对应 `data[i % SIZE]`
+///
+/// head和tail单调递增（按usize回绕），`tail - head` 为队列中的元素个数，最多为SIZE。
+/// 生产者写入 `data[tail % SIZE]` 后以Release写tail，消费者以Acquire读tail后才读取数据；
+/// 消费者读取完成后以Release写head，生产者以Acquire读head后才覆盖该槽。
+#[repr(C)]
+pub struct SafeRingBuffer<T, const SIZE: usize> {
+    head: CachePaddedIndex,
+    tail: CachePaddedIndex,
+    data: UnsafeCell<[T; SIZE]>,
+}
+
+impl<T, const SIZE: usize> Default for SafeRingBuffer<T, SIZE> where T: Default + Copy + Clone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const SIZE: usize> SafeRingBuffer<T, SIZE> where T: Default + Copy + Clone {
+    // head和tail只增不减并在usize::MAX处回绕，SIZE为2的幂时下标在回绕前后才连续。
+    // 共享内存中的队列不经过new，断言放在push_safe和pop_safe使用的掩码中
+    const MASK: usize = {
+        assert!(SIZE.is_power_of_two(), "SafeRingBuffer SIZE must be a power of two");
+        SIZE - 1
+    };
+
+    pub fn new() -> Self {
+        Self {
+            head: CachePaddedIndex(AtomicUsize::new(0)),
+            tail: CachePaddedIndex(AtomicUsize::new(0)),
+            data: UnsafeCell::new([T::default(); SIZE]),
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.tail.0.load(Ordering::Acquire).wrapping_sub(self.head.0.load(Ordering::Acquire))
+    }
+
+    // 消费者调用
+    #[inline]
+    pub fn pop_safe(&self) -> Option<T> {
+        let head = self.head.0.load(Ordering::Relaxed);
+        let tail = self.tail.0.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let item = unsafe { core::ptr::read_volatile((self.data.get() as *const T).add(head & Self::MASK)) };
+        self.head.0.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+
+    // 生产者调用
+    #[inline]
+    #[allow(clippy::result_unit_err)]
+    pub fn push_safe(&self, item: &T) -> Result<(), ()> {
+        let tail = self.tail.0.load(Ordering::Relaxed);
+        let head = self.head.0.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= SIZE {
+            return Err(());
+        }
+        unsafe { core::ptr::write_volatile((self.data.get() as *mut T).add(tail & Self::MASK), *item) };
+        self.tail.0.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+
+    #[test]
+    fn ring_buffer_keeps_one_slot_free() {
+        let mut ring = RingBuffer::<u32, 4>::new();
+        for i in 0..3 {
+            assert!(ring.push(&i).is_ok());
+        }
+        assert!(ring.full());
+        assert!(ring.push(&3).is_err());
+        assert_eq!(ring.size(), 3);
+    }
+
+    #[test]
+    fn ring_buffer_wraparound() {
+        let mut ring = RingBuffer::<usize, 8>::new();
+        for i in 0..100 {
+            ring.push(&i).unwrap();
+            ring.push(&(i + 1000)).unwrap();
+            assert_eq!(ring.pop(), Some(i));
+            assert_eq!(ring.pop(), Some(i + 1000));
+            assert!(ring.empty());
+        }
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn safe_ring_buffer_layout() {
+        let ring = Box::new(SafeRingBuffer::<u64, MAX_ITEM_NUM>::new());
+        let base = &*ring as *const _ as usize;
+        assert_eq!(&ring.head as *const _ as usize - base, 0);
+        assert_eq!(&ring.tail as *const _ as usize - base, 64);
+        assert_eq!(ring.data.get() as usize - base, 128);
+    }
+
+    #[test]
+    fn safe_ring_buffer_full_at_max_item_num() {
+        let ring = Box::new(SafeRingBuffer::<u64, MAX_ITEM_NUM>::new());
+        for i in 0..MAX_ITEM_NUM as u64 {
+            assert!(ring.push_safe(&i).is_ok());
+        }
+        assert_eq!(ring.size(), MAX_ITEM_NUM);
+        assert!(ring.push_safe(&0).is_err());
+        assert_eq!(ring.pop_safe(), Some(0));
+        assert!(ring.push_safe(&(MAX_ITEM_NUM as u64)).is_ok());
+        assert!(ring.push_safe(&0).is_err());
+        for i in 1..=MAX_ITEM_NUM as u64 {
+            assert_eq!(ring.pop_safe(), Some(i));
+        }
+        assert_eq!(ring.pop_safe(), None);
+        assert_eq!(ring.size(), 0);
+    }
+
+    #[test]
+    fn safe_ring_buffer_wraparound() {
+        // 多次越过MAX_ITEM_NUM，元素保持先进先出
+        let ring = Box::new(SafeRingBuffer::<u64, MAX_ITEM_NUM>::new());
+        let mut next_push = 0u64;
+        let mut next_pop = 0u64;
+        for round in 0..10 {
+            let count = MAX_ITEM_NUM / 2 + round * 37;
+            for _ in 0..count {
+                ring.push_safe(&next_push).unwrap();
+                next_push += 1;
+            }
+            while let Some(item) = ring.pop_safe() {
+                assert_eq!(item, next_pop);
+                next_pop += 1;
+            }
+        }
+        assert_eq!(next_push, next_pop);
+        assert!(next_push as usize > 2 * MAX_ITEM_NUM);
+    }
+
+    #[test]
+    fn safe_ring_buffer_index_overflow() {
+        // head和tail按usize回绕
+        let ring = SafeRingBuffer::<u32, 8>::new();
+        ring.head.0.store(usize::MAX - 2, Ordering::Relaxed);
+        ring.tail.0.store(usize::MAX - 2, Ordering::Relaxed);
+        for i in 0..8 {
+            ring.push_safe(&i).unwrap();
+        }
+        assert_eq!(ring.size(), 8);
+        assert!(ring.push_safe(&8).is_err());
+        for i in 0..8 {
+            assert_eq!(ring.pop_safe(), Some(i));
+        }
+        assert_eq!(ring.pop_safe(), None);
+    }
+}
@@ -0,0 +1,557 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use log::debug;
+use alloc::boxed::Box;
+use crate::async_runtime::{coroutine_get_current, coroutine_wake, coroutine_cancel, coroutine_spawn_with_owner, CoroutineId};
+use crate::async_runtime::new_buffer::{AsyncRing, IPCItem, NewBufferMap, MAX_IPC_MSG_LEN};
+use crate::async_runtime::async_syscall_handler::{post_async_syscall_reply, set_async_error, set_async_invalid_capability};
+use crate::async_runtime::utils::yield_now;
+use crate::async_runtime::timer::sleep_until;
+use crate::common::{message_info::{AsyncMessageLabel, AsyncErrorLabel, seL4_MessageInfo_t}, structures::exception_t, fault::{FaultType, seL4_Fault_t}, sel4_config::{seL4_InvalidCapability, seL4_IllegalOperation}};
+use crate::common::utils::convert_to_mut_type_ref;
+use crate::cspace::interface::CapTag;
+use crate::task_manager::{tcb_t, set_thread_state, possible_switch_to, ThreadState, badgeRegister, msgInfoRegister, msgRegister, n_msgRegisters};
+use crate::task_manager::ipc::{endpoint_t, EPState};
+
+// 异步endpoint IPC
+// 用户态通过NewBuffer提交Send/NBSend/Call/Recv请求，每个请求由一个独立的内核协程处理，
+// 协程在endpoint上无法完成rendezvous时挂起，由同步IPC路径在endpoint状态变化时唤醒。
+// 异步Call按调用等待回复：由同步Recv收到的Call通过服务端线程的SysReply回复（与reply cap一样，服务端重新Recv时被丢弃），
+// 由异步Recv收到的Call（无论调用方是异步Call还是同步Call）带有reply token，服务端通过EndpointReply请求回复，
+// 可以同时持有多个未回复的调用；异步Recv不使用提交线程的caller槽。
+// reply token：异步Call为其协程Id加1，同步Call为ASYNC_SYNC_TOKEN_BASE加序号，0表示不需要回复
+
+// item布局：
+// 请求：extend_msg[0] = ep_cptr（EndpointReply为reply token）, [1] = 忽略, [2] = label, [3] = length, [4] = 忽略, [5] = 忽略, [6..] = words
+// 响应：extend_msg[0] = AsyncErrorLabel, [1] = 忽略, [2] = label, [3] = length, [4] = badge,
+//       [5] = reply token（Recv收到的是Call时非0，否则为0）, [6..] = words
+// 失败时响应的布局与其他异步系统调用的错误一致（extend_msg[1]为seL4错误类型，见set_async_syscall_error），
+// 被服务端丢弃的Call以Cancelled完成
+// V1布局的item中words会被截断为u16
+const ASYNC_IPC_MSG_OFFSET: usize = 6;
+const ASYNC_IPC_BADGE: usize = 4;
+const ASYNC_IPC_REPLY_TOKEN: usize = 5;
+pub const ASYNC_IPC_MAX_WORDS: usize = MAX_IPC_MSG_LEN - ASYNC_IPC_MSG_OFFSET;
+
+pub struct AsyncIPCMessage {
+    pub error: AsyncErrorLabel,
+    // error为SyscallError时的seL4错误类型
+    pub error_type: usize,
+    pub badge: usize,
+    pub label: usize,
+    pub words: Vec<usize>,
+    // 异步Recv收到Call时为等待回复的调用
+    pub reply_token: Option<usize>,
+}
+
+impl AsyncIPCMessage {
+    fn from_item(item: &IPCItem) -> Self {
+        let length = core::cmp::min(item.extend_msg[3] as usize, ASYNC_IPC_MAX_WORDS);
+        let mut words = Vec::with_capacity(length);
+        for i in 0..length {
+            words.push(item.extend_msg[ASYNC_IPC_MSG_OFFSET + i] as usize);
+        }
+        Self { error: AsyncErrorLabel::NoError, error_type: 0, badge: 0, label: item.extend_msg[2] as usize, words, reply_token: None }
+    }
+
+    // 从线程的消息寄存器和IPC buffer中收集消息
+    fn from_thread(thread: &tcb_t, badge: usize) -> Self {
+        if thread.tcbFault.get_fault_type() != FaultType::NullFault {
+            // 异步接收端不支持fault消息的内容传递，只给出fault类型
+            return Self { error: AsyncErrorLabel::NoError, error_type: 0, badge, label: thread.tcbFault.get_fault_type() as usize, words: Vec::new(), reply_token: None };
+        }
+        let tag = seL4_MessageInfo_t::from_word_security(thread.get_register(msgInfoRegister));
+        let length = core::cmp::min(tag.get_length(), ASYNC_IPC_MAX_WORDS);
+        let ipc_buffer = thread.lookup_ipc_buffer(false);
+        let mut words = Vec::with_capacity(length);
+        for i in 0..length {
+            if i < n_msgRegisters {
+                words.push(thread.get_register(msgRegister[i]));
+            } else if let Some(buffer) = ipc_buffer {
+                words.push(buffer.msg[i]);
+            } else {
+                break;
+            }
+        }
+        Self { error: AsyncErrorLabel::NoError, error_type: 0, badge, label: tag.get_usize_label(), words, reply_token: None }
+    }
+
+    fn completed() -> Self {
+        Self { error: AsyncErrorLabel::NoError, error_type: 0, badge: 0, label: 0, words: Vec::new(), reply_token: None }
+    }
+
+    fn failed(error_type: usize) -> Self {
+        Self { error: AsyncErrorLabel::SyscallError, error_type, badge: 0, label: 0, words: Vec::new(), reply_token: None }
+    }
+
+    fn cancelled() -> Self {
+        Self { error: AsyncErrorLabel::Cancelled, error_type: 0, badge: 0, label: 0, words: Vec::new(), reply_token: None }
+    }
+
+    fn store_to_item(&self, item: &mut IPCItem) {
+        match self.error {
+            AsyncErrorLabel::NoError => {}
+            // endpoint cap查找失败时cap编号为0
+            AsyncErrorLabel::SyscallError if self.error_type == seL4_InvalidCapability => {
+                return set_async_invalid_capability(item, 0);
+            }
+            AsyncErrorLabel::SyscallError => return set_async_error(item, self.error_type),
+            error => {
+                item.extend_msg[0] = error.into();
+                return;
+            }
+        }
+        item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+        item.extend_msg[ASYNC_IPC_BADGE] = self.badge as u64;
+        item.extend_msg[ASYNC_IPC_REPLY_TOKEN] = self.reply_token.unwrap_or(0) as u64;
+        item.extend_msg[2] = self.label as u64;
+        item.extend_msg[3] = self.words.len() as u64;
+        for (i, w) in self.words.iter().enumerate() {
+            item.extend_msg[ASYNC_IPC_MSG_OFFSET + i] = *w as u64;
+        }
+    }
+
+    // 将消息写入线程，并设置msgInfo和badge寄存器
+    fn deliver_to_thread(&self, receiver: &mut tcb_t) {
+        let mut length = 0;
+        for (i, w) in self.words.iter().enumerate() {
+            length = receiver.set_mr(i, *w);
+        }
+        receiver.set_register(msgInfoRegister, seL4_MessageInfo_t::new(self.label, 0, 0, length).to_word());
+        receiver.set_register(badgeRegister, self.badge);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AsyncEPWaiter {
+    cid: CoroutineId,
+    is_recv: bool,
+    owner: usize,
+}
+
+// 挂起在endpoint上的异步协程，key为endpoint的指针
+static mut ASYNC_EP_WAITERS: BTreeMap<usize, VecDeque<AsyncEPWaiter>> = BTreeMap::new();
+// 等待回复的异步Call协程（即reply token），value为服务端tcb的指针
+static mut ASYNC_REPLY_WAITERS: BTreeMap<CoroutineId, usize> = BTreeMap::new();
+// 服务端线程通过同步Recv收到、等待其SysReply的异步Call，key为服务端tcb的指针
+static mut ASYNC_SYNC_REPLIES: BTreeMap<usize, CoroutineId> = BTreeMap::new();
+// 异步Recv收到、等待EndpointReply的同步Call，key为reply token
+static mut ASYNC_SYNC_CALLERS: BTreeMap<usize, AsyncSyncCaller> = BTreeMap::new();
+static mut ASYNC_SYNC_TOKEN_NEXT: usize = ASYNC_SYNC_TOKEN_BASE;
+// 同步Call的reply token从协程Id的取值范围之上开始分配
+const ASYNC_SYNC_TOKEN_BASE: usize = 1 << 32;
+
+#[derive(Clone, Copy)]
+struct AsyncSyncCaller {
+    // 阻塞在BlockedOnReply的调用线程
+    caller: usize,
+    // 收到该调用的服务端线程（异步Recv的提交线程）
+    server: usize,
+}
+// 交付给协程的消息（异步Recv收到的消息或异步Call收到的回复）
+static mut ASYNC_IPC_MSGS: BTreeMap<CoroutineId, AsyncIPCMessage> = BTreeMap::new();
+// 正在处理的异步endpoint请求，key为(NewBuffer地址, 请求item的cid)
+static mut ASYNC_EP_REQUESTS: BTreeMap<(usize, CoroutineId), AsyncEPRequest> = BTreeMap::new();
+
+#[derive(Clone, Copy)]
+struct AsyncEPRequest {
+    // 处理请求的协程
+    cid: CoroutineId,
+    msg_info: u32,
+    // 截止时间的定时协程
+    timer: Option<CoroutineId>,
+}
+
+async fn park_on_endpoint(ep_ptr: usize, is_recv: bool, owner: usize) {
+    let waiter = AsyncEPWaiter { cid: coroutine_get_current(), is_recv, owner };
+    unsafe {
+        ASYNC_EP_WAITERS.entry(ep_ptr).or_insert_with(VecDeque::new).push_back(waiter);
+    }
+    yield_now().await;
+}
+
+fn take_parked_waiter(ep_ptr: usize, is_recv: bool) -> Option<AsyncEPWaiter> {
+    unsafe {
+        let queue = ASYNC_EP_WAITERS.get_mut(&ep_ptr)?;
+        let pos = queue.iter().position(|w| w.is_recv == is_recv)?;
+        let waiter = queue.remove(pos);
+        if queue.is_empty() {
+            ASYNC_EP_WAITERS.remove(&ep_ptr);
+        }
+        waiter
+    }
+}
+
+fn remove_parked_waiter(ep_ptr: usize, cid: CoroutineId) {
+    unsafe {
+        if let Some(queue) = ASYNC_EP_WAITERS.get_mut(&ep_ptr) {
+            queue.retain(|w| w.cid != cid);
+            if queue.is_empty() {
+                ASYNC_EP_WAITERS.remove(&ep_ptr);
+            }
+        }
+    }
+}
+
+// endpoint状态发生变化（线程阻塞在endpoint上或endpoint被删除）时唤醒挂起的异步协程，
+// 每个挂起记录只会被唤醒一次
+pub fn async_endpoint_wake(ep_ptr: usize) {
+    unsafe {
+        if let Some(queue) = ASYNC_EP_WAITERS.remove(&ep_ptr) {
+            for waiter in queue.iter() {
+                coroutine_wake(&waiter.cid);
+            }
+        }
+    }
+}
+
+// 服务端线程SysReply且caller槽中没有reply cap时调用，将回复交付给通过同步Recv收到的异步Call
+pub fn async_endpoint_reply(server: &mut tcb_t) -> bool {
+    unsafe {
+        if let Some(cid) = ASYNC_SYNC_REPLIES.remove(&server.get_ptr()) {
+            ASYNC_REPLY_WAITERS.remove(&cid);
+            ASYNC_IPC_MSGS.insert(cid, AsyncIPCMessage::from_thread(server, 0));
+            coroutine_wake(&cid);
+            return true;
+        }
+    }
+    false
+}
+
+// 服务端线程重新Recv（丢弃caller cap）时调用，通过同步Recv收到的未回复异步Call以Cancelled结束
+pub fn async_endpoint_abort_reply(server: &mut tcb_t) {
+    unsafe {
+        if let Some(cid) = ASYNC_SYNC_REPLIES.remove(&server.get_ptr()) {
+            ASYNC_REPLY_WAITERS.remove(&cid);
+            ASYNC_IPC_MSGS.insert(cid, AsyncIPCMessage::cancelled());
+            coroutine_wake(&cid);
+        }
+    }
+}
+
+// 服务端线程被删除时调用，所有等待其回复的异步Call以Cancelled结束，
+// 等待其回复的同步Call与reply cap被删除时一样保持阻塞
+pub fn async_endpoint_release_server(server: &mut tcb_t) {
+    let server_ptr = server.get_ptr();
+    unsafe {
+        ASYNC_SYNC_REPLIES.remove(&server_ptr);
+        ASYNC_SYNC_CALLERS.retain(|_, caller| caller.server != server_ptr);
+        ASYNC_REPLY_WAITERS.retain(|cid, ptr| {
+            if *ptr != server_ptr {
+                return true;
+            }
+            ASYNC_IPC_MSGS.insert(*cid, AsyncIPCMessage::cancelled());
+            coroutine_wake(cid);
+            false
+        });
+    }
+}
+
+// 同步调用线程的BlockedOnReply被取消（cancel_ipc）时调用，使其reply token失效
+pub fn async_endpoint_drop_caller(caller: &tcb_t) {
+    let caller_ptr = caller.get_ptr();
+    unsafe {
+        ASYNC_SYNC_CALLERS.retain(|_, sync_caller| sync_caller.caller != caller_ptr);
+    }
+}
+
+// 为异步Recv收到的同步Call分配reply token，调用线程阻塞等待EndpointReply
+fn async_sync_caller_token(caller: &mut tcb_t, server: &tcb_t) -> usize {
+    set_thread_state(caller, ThreadState::ThreadStateBlockedOnReply);
+    unsafe {
+        let token = ASYNC_SYNC_TOKEN_NEXT;
+        ASYNC_SYNC_TOKEN_NEXT = ASYNC_SYNC_TOKEN_NEXT.wrapping_add(1).max(ASYNC_SYNC_TOKEN_BASE);
+        ASYNC_SYNC_CALLERS.insert(token, AsyncSyncCaller { caller: caller.get_ptr(), server: server.get_ptr() });
+        token
+    }
+}
+
+// 清理被取消协程在endpoint上的挂起记录和未取走的消息
+pub fn async_endpoint_cancel(cancelled: &[CoroutineId]) {
+    unsafe {
+        ASYNC_EP_WAITERS.retain(|_, queue| {
+            queue.retain(|w| !cancelled.contains(&w.cid));
+            !queue.is_empty()
+        });
+        ASYNC_REPLY_WAITERS.retain(|cid, _| !cancelled.contains(cid));
+        ASYNC_SYNC_REPLIES.retain(|_, cid| !cancelled.contains(cid));
+        ASYNC_EP_REQUESTS.retain(|_, request| !cancelled.contains(&request.cid));
+        for cid in cancelled {
+            ASYNC_IPC_MSGS.remove(cid);
+        }
+    }
+}
+
+fn lookup_async_endpoint(tcb: &tcb_t, ep_cptr: usize, is_recv: bool) -> Option<(&'static mut endpoint_t, usize, bool, bool)> {
+    let lu_ret = tcb.lookup_slot(ep_cptr);
+    if lu_ret.status != exception_t::EXCEPTION_NONE {
+        debug!("async_endpoint: lookup endpoint cap failed");
+        return None;
+    }
+    let cap = unsafe { (*lu_ret.slot).cap };
+    if cap.get_cap_type() != CapTag::CapEndpointCap {
+        debug!("async_endpoint: cap is not an endpoint cap");
+        return None;
+    }
+    let right = if is_recv { cap.get_ep_can_receive() } else { cap.get_ep_can_send() };
+    if right == 0 {
+        debug!("async_endpoint: endpoint cap lacks the required right");
+        return None;
+    }
+    Some((convert_to_mut_type_ref::<endpoint_t>(cap.get_ep_ptr()), cap.get_ep_badge(),
+          cap.get_ep_can_grant() != 0, cap.get_ep_can_grant_reply() != 0))
+}
+
+async fn wait_for_message(cid: CoroutineId) -> AsyncIPCMessage {
+    loop {
+        if let Some(msg) = unsafe { ASYNC_IPC_MSGS.remove(&cid) } {
+            return msg;
+        }
+        yield_now().await;
+    }
+}
+
+// 为一个endpoint请求创建处理协程
+// map_ptr为ring的注册项，ring被注销时其上的endpoint协程都被取消
+pub fn async_endpoint_spawn(item: IPCItem, tcb: &mut tcb_t, map_ptr: usize) {
+    let owner = convert_to_mut_type_ref::<tcb_t>(tcb.get_ptr());
+    let cid = coroutine_spawn_with_owner(Box::pin(async_endpoint_handler(item, owner, map_ptr)), tcb.get_ptr());
+    unsafe {
+        ASYNC_EP_REQUESTS.insert((NewBufferMap::from_ptr(map_ptr).buf.get_ptr(), item.cid), AsyncEPRequest { cid, msg_info: item.msg_info, timer: None });
+    }
+}
+
+// 移除请求记录，同时取消其定时协程
+fn take_async_ep_request(ring: &AsyncRing, item_cid: CoroutineId) -> Option<AsyncEPRequest> {
+    let request = unsafe { ASYNC_EP_REQUESTS.remove(&(ring.get_ptr(), item_cid))? };
+    if let Some(timer) = request.timer {
+        coroutine_cancel(&timer);
+    }
+    Some(request)
+}
+
+// 取消正在处理的endpoint请求，返回被取消请求的msg_info，请求已完成时返回None
+pub fn async_endpoint_cancel_request(ring: &AsyncRing, item_cid: CoroutineId) -> Option<u32> {
+    let request = take_async_ep_request(ring, item_cid)?;
+    coroutine_cancel(&request.cid);
+    async_endpoint_cancel(&[request.cid]);
+    Some(request.msg_info)
+}
+
+// 取消ring上所有正在处理的endpoint请求，ring被注销时调用，不再写回响应
+pub fn async_endpoint_cancel_ring(ring: &AsyncRing) {
+    let mut cancelled = Vec::new();
+    unsafe {
+        ASYNC_EP_REQUESTS.retain(|(ptr, _), request| {
+            if *ptr != ring.get_ptr() {
+                return true;
+            }
+            cancelled.push(request.cid);
+            if let Some(timer) = request.timer {
+                coroutine_cancel(&timer);
+            }
+            false
+        });
+    }
+    for cid in cancelled.iter() {
+        coroutine_cancel(cid);
+    }
+    async_endpoint_cancel(&cancelled);
+}
+
+// 为正在处理的endpoint请求设置截止时间（time寄存器的值），重复设置时以最后一次为准，
+// 请求已完成时返回false
+pub fn async_endpoint_set_deadline(map_ptr: usize, item_cid: CoroutineId, deadline: usize, owner: usize) -> bool {
+    let request = match unsafe { ASYNC_EP_REQUESTS.get_mut(&(NewBufferMap::from_ptr(map_ptr).buf.get_ptr(), item_cid)) } {
+        Some(request) => request,
+        None => return false,
+    };
+    if let Some(timer) = request.timer {
+        coroutine_cancel(&timer);
+    }
+    request.timer = Some(coroutine_spawn_with_owner(Box::pin(async_endpoint_deadline(map_ptr, item_cid, deadline)), owner));
+    true
+}
+
+// 截止时间到达时请求仍未完成，取消处理协程并以TimedOut完成
+async fn async_endpoint_deadline(map_ptr: usize, item_cid: CoroutineId, deadline: usize) {
+    sleep_until(deadline).await;
+    let request = match unsafe { ASYNC_EP_REQUESTS.remove(&(NewBufferMap::from_ptr(map_ptr).buf.get_ptr(), item_cid)) } {
+        Some(request) => request,
+        None => return,
+    };
+    debug!("async_endpoint_deadline: request {:?} timed out", item_cid);
+    coroutine_cancel(&request.cid);
+    async_endpoint_cancel(&[request.cid]);
+    let mut item = IPCItem::from(item_cid, request.msg_info);
+    item.extend_msg[0] = AsyncErrorLabel::TimedOut.into();
+    post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &item);
+}
+
+async fn async_endpoint_handler(mut item: IPCItem, tcb: &'static mut tcb_t, map_ptr: usize) {
+    let label = AsyncMessageLabel::from(item.msg_info);
+    let result = match label {
+        AsyncMessageLabel::EndpointSend => handle_async_endpoint_send(&item, tcb, true, false).await,
+        AsyncMessageLabel::EndpointNBSend => handle_async_endpoint_send(&item, tcb, false, false).await,
+        AsyncMessageLabel::EndpointCall => handle_async_endpoint_send(&item, tcb, true, true).await,
+        AsyncMessageLabel::EndpointRecv => handle_async_endpoint_recv(&item, tcb).await,
+        AsyncMessageLabel::EndpointReply => handle_async_endpoint_reply(&item, tcb),
+        _ => AsyncIPCMessage::failed(seL4_IllegalOperation),
+    };
+    result.store_to_item(&mut item);
+    let map = NewBufferMap::from_ptr(map_ptr);
+    take_async_ep_request(&map.buf, item.cid);
+    post_async_syscall_reply(map, &item);
+}
+
+async fn handle_async_endpoint_send(item: &IPCItem, tcb: &mut tcb_t, blocking: bool, do_call: bool) -> AsyncIPCMessage {
+    let ep_cptr = item.extend_msg[0] as usize;
+    let cid = coroutine_get_current();
+    let mut msg = AsyncIPCMessage::from_item(item);
+    loop {
+        // 每次重试都重新查找cap，挂起期间cap可能已被删除
+        let (ep, badge, can_grant, can_grant_reply) = match lookup_async_endpoint(tcb, ep_cptr, false) {
+            Some(ret) => ret,
+            None => return AsyncIPCMessage::failed(seL4_InvalidCapability),
+        };
+        if do_call && !(can_grant || can_grant_reply) {
+            debug!("handle_async_endpoint_send: call without grant right can not be replied");
+            return AsyncIPCMessage::failed(seL4_IllegalOperation);
+        }
+        msg.badge = badge;
+        let server = if ep.get_state() == EPState::Recv {
+            let mut queue = ep.get_queue();
+            let dest_thread = convert_to_mut_type_ref::<tcb_t>(queue.head);
+            queue.ep_dequeue(dest_thread);
+            ep.set_queue(&queue);
+            if queue.empty() {
+                ep.set_state(EPState::Idle as usize);
+            }
+            msg.deliver_to_thread(dest_thread);
+            set_thread_state(dest_thread, ThreadState::ThreadStateRunning);
+            possible_switch_to(dest_thread);
+            if do_call {
+                // 同步Recv收到的Call由服务端线程的SysReply回复
+                unsafe { ASYNC_SYNC_REPLIES.insert(dest_thread.get_ptr(), cid); }
+            }
+            Some(dest_thread.get_ptr())
+        } else if let Some(waiter) = take_parked_waiter(ep.get_ptr(), true) {
+            let owner = waiter.owner;
+            if do_call {
+                msg.reply_token = Some(cid.0 as usize + 1);
+            }
+            unsafe { ASYNC_IPC_MSGS.insert(waiter.cid, core::mem::replace(&mut msg, AsyncIPCMessage::completed())); }
+            coroutine_wake(&waiter.cid);
+            Some(owner)
+        } else {
+            None
+        };
+        match server {
+            Some(server) => {
+                if !do_call {
+                    return AsyncIPCMessage::completed();
+                }
+                unsafe { ASYNC_REPLY_WAITERS.insert(cid, server); }
+                return wait_for_message(cid).await;
+            }
+            None => {
+                if !blocking {
+                    return AsyncIPCMessage::completed();
+                }
+                park_on_endpoint(ep.get_ptr(), false, tcb.get_ptr()).await;
+                remove_parked_waiter(ep.get_ptr(), cid);
+            }
+        }
+    }
+}
+
+async fn handle_async_endpoint_recv(item: &IPCItem, tcb: &mut tcb_t) -> AsyncIPCMessage {
+    let ep_cptr = item.extend_msg[0] as usize;
+    let cid = coroutine_get_current();
+    loop {
+        let (ep, _, _, _) = match lookup_async_endpoint(tcb, ep_cptr, true) {
+            Some(ret) => ret,
+            None => return AsyncIPCMessage::failed(seL4_InvalidCapability),
+        };
+        if ep.get_state() == EPState::Send {
+            let mut queue = ep.get_queue();
+            let sender = convert_to_mut_type_ref::<tcb_t>(queue.head);
+            queue.ep_dequeue(sender);
+            ep.set_queue(&queue);
+            if queue.empty() {
+                ep.set_state(EPState::Idle as usize);
+            }
+            let badge = sender.tcbState.get_blocking_ipc_badge();
+            let can_grant = sender.tcbState.get_blocking_ipc_can_grant() != 0;
+            let can_grant_reply = sender.tcbState.get_blocking_ipc_can_grant_reply() != 0;
+            let mut msg = AsyncIPCMessage::from_thread(sender, badge);
+            if sender.tcbState.get_blocking_ipc_is_call() != 0 {
+                // 同步Call不占用提交线程的caller槽，而是分配reply token，由服务端通过EndpointReply回复
+                if can_grant || can_grant_reply {
+                    msg.reply_token = Some(async_sync_caller_token(sender, tcb));
+                } else {
+                    set_thread_state(sender, ThreadState::ThreadStateInactive);
+                }
+            } else {
+                set_thread_state(sender, ThreadState::ThreadStateRunning);
+                possible_switch_to(sender);
+            }
+            return msg;
+        }
+        // 让挂起的异步发送者重试，它们会把消息直接交给本协程
+        let ep_ptr = ep.get_ptr();
+        while let Some(waiter) = take_parked_waiter(ep_ptr, false) {
+            coroutine_wake(&waiter.cid);
+        }
+        park_on_endpoint(ep_ptr, true, tcb.get_ptr()).await;
+        remove_parked_waiter(ep_ptr, cid);
+        if let Some(msg) = unsafe { ASYNC_IPC_MSGS.remove(&cid) } {
+            return msg;
+        }
+    }
+}
+
+// EndpointReply：回复reply token对应的调用，只有收到该调用的服务端线程可以回复
+fn handle_async_endpoint_reply(item: &IPCItem, tcb: &mut tcb_t) -> AsyncIPCMessage {
+    let token = item.extend_msg[0] as usize;
+    if token == 0 {
+        return AsyncIPCMessage::failed(seL4_IllegalOperation);
+    }
+    if token >= ASYNC_SYNC_TOKEN_BASE {
+        return reply_to_sync_caller(token, item, tcb);
+    }
+    let cid = CoroutineId((token - 1) as u32);
+    unsafe {
+        if ASYNC_REPLY_WAITERS.get(&cid) != Some(&tcb.get_ptr()) {
+            debug!("handle_async_endpoint_reply: no call waiting for reply token {:#x}", token);
+            return AsyncIPCMessage::failed(seL4_IllegalOperation);
+        }
+        ASYNC_REPLY_WAITERS.remove(&cid);
+        ASYNC_SYNC_REPLIES.retain(|_, waiter| *waiter != cid);
+        ASYNC_IPC_MSGS.insert(cid, AsyncIPCMessage::from_item(item));
+    }
+    coroutine_wake(&cid);
+    AsyncIPCMessage::completed()
+}
+
+// 回复异步Recv收到的同步Call，与SysReply一样唤醒调用线程
+fn reply_to_sync_caller(token: usize, item: &IPCItem, tcb: &mut tcb_t) -> AsyncIPCMessage {
+    let caller = match unsafe { ASYNC_SYNC_CALLERS.get(&token) } {
+        Some(caller) if caller.server == tcb.get_ptr() => convert_to_mut_type_ref::<tcb_t>(caller.caller),
+        _ => {
+            debug!("handle_async_endpoint_reply: no call waiting for reply token {:#x}", token);
+            return AsyncIPCMessage::failed(seL4_IllegalOperation);
+        }
+    };
+    unsafe { ASYNC_SYNC_CALLERS.remove(&token); }
+    if caller.tcbFault.get_fault_type() != FaultType::NullFault {
+        // fault消息只传递了fault类型，回复时不写回寄存器，直接重启线程
+        caller.tcbFault = seL4_Fault_t::new_null_fault();
+        set_thread_state(caller, ThreadState::ThreadStateRestart);
+    } else {
+        AsyncIPCMessage::from_item(item).deliver_to_thread(caller);
+        set_thread_state(caller, ThreadState::ThreadStateRunning);
+    }
+    possible_switch_to(caller);
+    AsyncIPCMessage::completed()
+}
@@ -1,21 +1,33 @@
 use crate::BIT;
 use crate::MASK;
 use log::debug;
-use crate::async_runtime::new_buffer::{NewBuffer, IPCItem};
+use crate::async_runtime::new_buffer::{AsyncRing, IPCItem, AsyncNotifyPolicy, AsyncNotifyState, NewBufferMap, MAX_IPC_MSG_LEN};
+use crate::async_runtime::NEW_BUFFER_MAP;
+use crate::async_runtime::polling::{async_polling_active, async_polling_on};
 use crate::async_runtime::utils::yield_now;
+use crate::async_runtime::{coroutine_wake, coroutine_get_current, coroutine_run_source, async_syscall_is_registered, async_syscall_vspace_of, CoroutineId};
+use crate::async_runtime::async_endpoint::{async_endpoint_spawn, async_endpoint_cancel_request, async_endpoint_set_deadline};
+use crate::async_runtime::timer::coroutine_now;
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
 use crate::common::{utils::{convert_to_mut_type_ref, pageBitsForSize}, message_info::{AsyncMessageLabel, AsyncErrorLabel}, object::ObjectType, sbi::console_putchar, structures::exception_t, sel4_config::*};
-use crate::cspace::interface::{cap_t, cte_t, CapTag, seL4_CapRights_t};
+use crate::cspace::interface::{cap_t, cte_t, CapTag, seL4_CapRights_t, cte_move};
 use crate::task_manager::{tcb_t, get_currenct_thread, ipc::notification_t};
-use crate::uintr;
 use crate::uintr::uipi_send;
-use crate::vspace::{checkVPAlignment, kpptr_to_paddr, pptr_to_paddr, find_vspace_for_asid, vm_attributes_t, pte_t};
-use crate::uintc::{KERNEL_SENDER_POOL_IDX, NET_UINTR_IDX, UIntrReceiver, UIntrSTEntry};
+use crate::vspace::{checkVPAlignment, pptr_to_paddr, find_vspace_for_asid, vm_attributes_t, pte_t};
+use crate::uintc::{NET_UINTR_IDX, UIntrReceiver};
 use core::sync::atomic::Ordering::SeqCst;
 use core::intrinsics::unlikely;
-use crate::kernel::boot::current_syscall_error;
-use crate::syscall::{alignUp, FREE_INDEX_TO_OFFSET, GET_FREE_REF, invocation::{invoke_cnode::*, invoke_untyped::invoke_untyped_retype, invoke_mmu_op::*}, invocation::decode::decode_untyped_invocation::{check_object_type, check_cnode_slot}};
-use crate::syscall::utils::lookup_slot_for_cnode_op;
-use crate::config::USER_TOP;
+use crate::kernel::boot::{current_syscall_error, current_lookup_fault};
+use crate::common::fault::{LookupFaultType, lookup_fault_missing_capability_new};
+use crate::syscall::{alignUp, FREE_INDEX_TO_OFFSET, GET_FREE_REF, invocation::{invoke_cnode::*, invoke_untyped::{invoke_untyped_retype, invoke_untyped_retype_batch, RetypeBatchEntry}, invoke_mmu_op::*}, invocation::decode::decode_untyped_invocation::{check_object_type, check_cnode_slot}};
+use crate::syscall::utils::{lookup_slot_for_cnode_op, check_prio, check_ipc_buffer_vaild};
+use crate::syscall::invocation::{invoke_tcb::*, decode::decode_tcb_invocation::{decode_set_space_args, CopyRegisters_suspendSource,
+    CopyRegisters_resumeTarget, CopyRegisters_transferFrame, CopyRegisters_transferInteger, ReadRegisters_suspend}};
+use crate::syscall::is_valid_vtable_root;
+use crate::config::{n_frameRegisters, n_gpRegisters, frameRegisters, gpRegisters};
+use crate::task_manager::{NextIP, FaultIP, rescheduleRequired};
+use crate::config::{USER_TOP, TIMER_CLOCK_HZ};
 // 每个线程对应一个内核syscall handler协程
 // 每个线程在用户态只能发现自己的内核协程不在线
 // 当线程陷入内核去激活协程时，所有的内核协程都不在线（因为内核独占）
@@ -27,25 +39,81 @@ use crate::config::USER_TOP;
 
 
 
-pub async fn async_syscall_handler(ntfn_cap: cap_t, new_buffer_cap: cap_t, tcb: &mut tcb_t, sender_id: usize) {
+// 每个协程每轮最多处理的请求数
+const ASYNC_SYSCALL_BUDGET: usize = 32;
+
+// map_ptr为该ring在NEW_BUFFER_MAP中的注册项，注册项单独分配，注销前地址不变，注销时本协程同时被取消
+pub async fn async_syscall_handler(ntfn_cap: cap_t, map_ptr: usize, owner: &mut tcb_t) {
     // debug!("async_syscall_handler: enter");
+    let ring = NewBufferMap::from_ptr(map_ptr).buf;
     // 异常处理
-    let error_id: isize = -1;
-    if sender_id == (error_id as usize) {
+    if NewBufferMap::from_ptr(map_ptr).sender_id.is_none() {
         debug!("async_syscall_handler: fail to register sender!");
         return;
     }
-    let new_buffer = convert_to_mut_type_ref::<NewBuffer>(new_buffer_cap.get_frame_base_ptr());
-    // debug!("async_syscall_handler: new_buffer_cap: {}, new_buffer_ptr: {:#x}", new_buffer_cap.get_cap_ptr(), new_buffer_cap.get_frame_base_ptr());
+    // debug!("async_syscall_handler: new_buffer_ptr: {:#x}, version: {:?}", ring.get_ptr(), ring.version);
     let badge = ntfn_cap.get_nf_badge();
+    let mut budget = ASYNC_SYSCALL_BUDGET;
     loop {
-        if let Some(mut item) = new_buffer.req_items.get_first_item() {
+        if budget == 0 {
+            // 本轮处理的请求数达到上限，让出执行器，其他线程的协程按优先级得到执行
+            budget = ASYNC_SYSCALL_BUDGET;
+            coroutine_wake(&coroutine_get_current());
+            yield_now().await;
+        }
+        // 先取出本轮的请求，其中Cancel请求的目标不再执行
+        let mut batch = Vec::new();
+        while batch.len() < budget {
+            match ring.pop_request() {
+                Some(item) => batch.push(item),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            // debug!("handler: else");
+            if !async_polling_active() {
+                // 有轮询核时用户态不需要唤醒处理协程
+                ring.recv_req_status().store(false, SeqCst);
+            }
+            set_async_syscall_idle(NewBufferMap::from_ptr(map_ptr), true);
+            budget = ASYNC_SYSCALL_BUDGET;
+            yield_now().await;
+            // debug!("wake recv co");
+            continue;
+        }
+        budget -= batch.len();
+        set_async_syscall_idle(NewBufferMap::from_ptr(map_ptr), false);
+        let start = coroutine_now();
+        record_async_batch(NewBufferMap::from_ptr(map_ptr), &batch);
+        let queued: BTreeSet<CoroutineId> = batch.iter().map(|item| item.cid).collect();
+        let cancelled: BTreeSet<CoroutineId> = batch.iter()
+            .filter(|item| AsyncMessageLabel::from(item.msg_info) == AsyncMessageLabel::Cancel)
+            .map(|item| CoroutineId(item.extend_msg[0] as u32))
+            .collect();
+        let mut timeouts = Vec::new();
+        for mut item in batch {
             let label: AsyncMessageLabel = AsyncMessageLabel::from(item.msg_info);
             // debug!("async_syscall_handler: handle async syscall: {:?}", label);
+            if label != AsyncMessageLabel::Cancel && cancelled.contains(&item.cid) {
+                item.extend_msg[0] = AsyncErrorLabel::Cancelled.into();
+                post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &item);
+                continue;
+            }
+            // 共享ring中的请求以提交线程的身份执行
+            let tcb = match get_async_submitter(&mut item, owner, map_ptr) {
+                Some(tcb) => tcb,
+                None => {
+                    post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &item);
+                    continue;
+                }
+            };
             match label {
                 AsyncMessageLabel::UntypedRetype => {
                     handle_async_untyped_retype(&mut item, tcb);
                 }
+                AsyncMessageLabel::UntypedRetypeBatch => {
+                    handle_async_untyped_retype_batch(&mut item, tcb);
+                }
                 AsyncMessageLabel::RISCVPageGetAddress => {
                     handle_async_page_get_address(&mut item, tcb);
                 }
@@ -61,9 +129,54 @@ pub async fn async_syscall_handler(ntfn_cap: cap_t, new_buffer_cap: cap_t, tcb:
                 AsyncMessageLabel::TCBUnbindNotification => {
                     handle_async_tcb_unbind_notification(&mut item, tcb);
                 }
-                AsyncMessageLabel::CNodeRevoke | AsyncMessageLabel::CNodeRotate | AsyncMessageLabel::CNodeCancelBadgedSends | AsyncMessageLabel::CNodeDelete | AsyncMessageLabel::CNodeCopy | AsyncMessageLabel::CNodeMint => {
+                AsyncMessageLabel::TCBReadRegisters => {
+                    handle_async_tcb_read_registers(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBWriteRegisters => {
+                    handle_async_tcb_write_registers(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBCopyRegisters => {
+                    handle_async_tcb_copy_registers(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBConfigure => {
+                    handle_async_tcb_configure(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBSetPriority | AsyncMessageLabel::TCBSetMCPriority | AsyncMessageLabel::TCBSetSchedParams => {
+                    handle_async_tcb_set_sched(&mut item, tcb, label);
+                }
+                AsyncMessageLabel::TCBSetIPCBuffer => {
+                    handle_async_tcb_set_ipc_buffer(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBSetSpace => {
+                    handle_async_tcb_set_space(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBSuspend | AsyncMessageLabel::TCBResume => {
+                    handle_async_tcb_suspend_resume(&mut item, tcb, label);
+                }
+                AsyncMessageLabel::TCBSetAffinity => {
+                    handle_async_tcb_set_affinity(&mut item, tcb);
+                }
+                AsyncMessageLabel::TCBSetTLSBase => {
+                    handle_async_tcb_set_tls_base(&mut item, tcb);
+                }
+                AsyncMessageLabel::CNodeRevoke | AsyncMessageLabel::CNodeRotate | AsyncMessageLabel::CNodeCancelBadgedSends | AsyncMessageLabel::CNodeDelete | AsyncMessageLabel::CNodeCopy | AsyncMessageLabel::CNodeMint
+                | AsyncMessageLabel::CNodeMove | AsyncMessageLabel::CNodeMutate | AsyncMessageLabel::CNodeSaveCaller => {
                     handle_async_cnode_syscall(&mut item, tcb, label);
                 }
+                AsyncMessageLabel::EndpointSend | AsyncMessageLabel::EndpointNBSend
+                | AsyncMessageLabel::EndpointCall | AsyncMessageLabel::EndpointRecv | AsyncMessageLabel::EndpointReply => {
+                    // endpoint IPC可能阻塞，每个请求由独立的协程处理，完成后自行写回响应
+                    async_endpoint_spawn(item, tcb, map_ptr);
+                    continue;
+                }
+                AsyncMessageLabel::Cancel => {
+                    handle_async_cancel(&mut item, map_ptr, &queued);
+                }
+                AsyncMessageLabel::Timeout => {
+                    // 本轮的endpoint请求都创建协程后再设置，目标可以排在Timeout之后
+                    timeouts.push(item);
+                    continue;
+                }
                 AsyncMessageLabel::RISCVPageTableMap => {
                     handle_async_page_table_map(&mut item, tcb);
                 }
@@ -80,34 +193,175 @@ pub async fn async_syscall_handler(ntfn_cap: cap_t, new_buffer_cap: cap_t, tcb:
                     handle_async_unknown_label(&mut item, tcb);
                 }
             };
-            new_buffer.res_items.write_free_item(&item).unwrap();
-            if new_buffer.recv_reply_status.load(SeqCst) == false {
-                new_buffer.recv_reply_status.store(true, SeqCst);
-                // todo: send uintr
-                // debug!("async_syscall_handler: send uintr sender_id: {}", sender_id);
-                unsafe {
-                    send_async_syscall_uintr(sender_id);
-                }
+            if !async_syscall_is_registered(&coroutine_get_current()) {
+                // 本次请求删除了自己的线程、notification或buffer
+                return;
             }
-        } else {
-            // debug!("handler: else");
-            new_buffer.recv_req_status.store(false, SeqCst);
-            yield_now().await;
-            // debug!("wake recv co");
+            post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &item);
+        }
+        for mut item in timeouts {
+            handle_async_timeout(&mut item, map_ptr, owner);
+            post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &item);
+        }
+        NewBufferMap::from_ptr(map_ptr).stats.run_cycles += coroutine_now() - start;
+    }
+}
+
+// 统计一轮取出的请求，队列长度包括本轮取出的请求
+fn record_async_batch(map: &mut NewBufferMap, batch: &[IPCItem]) {
+    let stats = &mut map.stats;
+    stats.batches += 1;
+    stats.wakeups[coroutine_run_source() as usize] += 1;
+    stats.req_depth_max = core::cmp::max(stats.req_depth_max, batch.len() + map.buf.request_len());
+    for item in batch {
+        stats.labels[AsyncMessageLabel::from(item.msg_info) as usize] += 1;
+    }
+}
+
+// 写回响应并按通知策略通知用户态，map为响应所属ring的注册项
+pub fn post_async_syscall_reply(map: &mut NewBufferMap, item: &IPCItem) {
+    let ring = map.buf;
+    ring.push_response(item).unwrap();
+    let sender_id = match map.sender_id {
+        Some(sender_id) => sender_id,
+        None => return,
+    };
+    map.stats.res_depth_max = core::cmp::max(map.stats.res_depth_max, ring.response_len());
+    let state = &mut map.notify;
+    state.responses += 1;
+    if ring.recv_reply_status().load(SeqCst) {
+        // 用户态正在处理响应，会自己取走新的响应
+        state.pending = 0;
+        return;
+    }
+    state.pending += 1;
+    let drained = state.idle;
+    let notify = match state.policy {
+        AsyncNotifyPolicy::Immediate => true,
+        AsyncNotifyPolicy::Batch(n) => state.pending >= n || drained,
+        AsyncNotifyPolicy::Drain => drained,
+        AsyncNotifyPolicy::Timer(us) => {
+            if state.pending == 1 {
+                state.deadline = coroutine_now().saturating_add(us.saturating_mul(TIMER_CLOCK_HZ / 1000000));
+            }
+            false
         }
+    };
+    if notify {
+        notify_async_syscall_reply(&ring, state, sender_id);
     }
 }
 
-unsafe fn send_async_syscall_uintr(offset: usize) {
-    let uist_idx = *KERNEL_SENDER_POOL_IDX.lock();
-    let frame_addr = crate::uintc::UINTR_ST_POOL.as_ptr().offset((uist_idx * core::mem::size_of::<UIntrSTEntry>() * crate::uintc::config::UINTC_ENTRY_NUM) as isize) as usize;
-    uintr::suist::write((1 << 63) | (1 << 44) | (kpptr_to_paddr(frame_addr) >> 0xC));
+fn notify_async_syscall_reply(ring: &AsyncRing, state: &mut AsyncNotifyState, sender_id: usize) {
+    state.pending = 0;
+    if ring.recv_reply_status().load(SeqCst) == false {
+        ring.recv_reply_status().store(true, SeqCst);
+        state.uintr_sent += 1;
+        // debug!("async_syscall_handler: send uintr sender_id: {}", sender_id);
+        unsafe {
+            send_async_syscall_uintr(sender_id);
+        }
+    }
+}
+
+// 记录处理协程是否已处理完请求队列，处理完时通知Batch和Drain策略下剩余的响应
+fn set_async_syscall_idle(map: &mut NewBufferMap, idle: bool) {
+    map.notify.idle = idle;
+    if !idle {
+        return;
+    }
+    match (map.notify.policy, map.sender_id) {
+        (AsyncNotifyPolicy::Batch(_) | AsyncNotifyPolicy::Drain, Some(sender_id)) if map.notify.pending > 0 => {
+            notify_async_syscall_reply(&map.buf, &mut map.notify, sender_id);
+        }
+        _ => {}
+    }
+}
+
+// 时钟中断时调用，通知Timer策略下到期的响应
+pub fn async_syscall_notify_tick() {
+    let now = coroutine_now();
+    for map in unsafe { NEW_BUFFER_MAP.iter_mut() } {
+        if let (AsyncNotifyPolicy::Timer(_), Some(sender_id)) = (map.notify.policy, map.sender_id) {
+            if map.notify.pending > 0 && now >= map.notify.deadline {
+                notify_async_syscall_reply(&map.buf, &mut map.notify, sender_id);
+            }
+        }
+    }
+}
+
+pub unsafe fn send_async_syscall_uintr(offset: usize) {
+    crate::uintc::kernel_uist_activate();
     uipi_send(offset);
 }
 
+// Cancel请求：extend_msg[0]为目标请求的cid
+// 目标在本轮的请求中时直接跳过不执行，目标是正在处理的endpoint请求时取消其协程，
+// 两种情况下目标都以Cancelled完成；目标已完成时返回seL4_InvalidArgument
+fn handle_async_cancel(item: &mut IPCItem, map_ptr: usize, queued: &BTreeSet<CoroutineId>) {
+    let target = CoroutineId(item.extend_msg[0] as u32);
+    if queued.contains(&target) {
+        item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+        return;
+    }
+    match async_endpoint_cancel_request(&NewBufferMap::from_ptr(map_ptr).buf, target) {
+        Some(msg_info) => {
+            let mut cancelled = IPCItem::from(target, msg_info);
+            cancelled.extend_msg[0] = AsyncErrorLabel::Cancelled.into();
+            post_async_syscall_reply(NewBufferMap::from_ptr(map_ptr), &cancelled);
+            item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+        }
+        None => {
+            debug!("handle_async_cancel: request {:?} not found or already completed.", target);
+            set_async_invalid_argument(item, 0);
+        }
+    }
+}
+
+// 请求的提交者：msg_info的高16位为0时是注册ring的线程，
+// 否则为提交线程通过UintrAddAsyncSubmitter加入共享ring时得到的badge，请求以提交线程自己的cspace执行，
+// 不通过注册线程的cspace查找，共享地址空间不会得到注册线程的cap。
+// 同一地址空间中的线程可以互相写ring，也就可以使用其他已加入线程的badge，因此加入ring即表示信任该地址空间中的其他线程
+fn get_async_submitter(item: &mut IPCItem, owner: &mut tcb_t, map_ptr: usize) -> Option<&'static mut tcb_t> {
+    let badge = (item.msg_info >> 16) as usize;
+    if badge == 0 {
+        return Some(convert_to_mut_type_ref::<tcb_t>(owner.get_ptr()));
+    }
+    let map = NewBufferMap::from_ptr(map_ptr);
+    let submitter = match map.submitters.get(badge - 1) {
+        Some(tcb_ptr) if *tcb_ptr != 0 => convert_to_mut_type_ref::<tcb_t>(*tcb_ptr),
+        _ => {
+            debug!("get_async_submitter: unknown submitter badge {:#x}.", badge);
+            set_async_error(item, seL4_IllegalOperation);
+            return None;
+        }
+    };
+    // 提交线程加入后可能切换了地址空间
+    if map.vspace.is_none() || async_syscall_vspace_of(submitter) != map.vspace {
+        debug!("get_async_submitter: submitter is not in the address space of the ring.");
+        set_async_error(item, seL4_IllegalOperation);
+        return None;
+    }
+    Some(submitter)
+}
+
+// Timeout请求：extend_msg[0]为目标请求的cid，[1]为截止时间（time寄存器的值）
+// 目标是正在处理的endpoint请求时为其设置截止时间，到期仍未完成的目标被取消并以TimedOut完成；
+// 目标已完成或不是endpoint请求时返回seL4_InvalidArgument
+fn handle_async_timeout(item: &mut IPCItem, map_ptr: usize, tcb: &mut tcb_t) {
+    let target = CoroutineId(item.extend_msg[0] as u32);
+    let deadline = item.extend_msg[1] as usize;
+    if async_endpoint_set_deadline(map_ptr, target, deadline, tcb.get_ptr()) {
+        item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+    } else {
+        debug!("handle_async_timeout: request {:?} not found or already completed.", target);
+        set_async_invalid_argument(item, 0);
+    }
+}
+
 fn handle_async_unknown_label(item: &mut IPCItem, tcb: &mut tcb_t) {
     debug!("async_syscall_handler: TODO: handle unknown label");
-    item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+    set_async_error(item, seL4_IllegalOperation);
 }
 
 fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
@@ -116,7 +370,7 @@ fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
     let service_lu_ret = tcb.lookup_slot(service_cptr);
     if unlikely(service_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_untyped_retype: Invocation of invalid service cap {:#x}.", service_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let service_slot: &mut cte_t = unsafe {&mut *service_lu_ret.slot };
@@ -132,7 +386,7 @@ fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
     let op_new_type = ObjectType::from_usize(new_type_usize);
     if op_new_type.is_none() {
         debug!("handler_untyped_retype: Untyped Retype: Invalid object type. {}", new_type_usize);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_invalid_argument(item, 0);
         return;
     }
     let new_type = op_new_type.unwrap();
@@ -140,24 +394,24 @@ fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
     // TODO: Translate
     if user_obj_size >= wordBits || obj_size > seL4_MaxUntypedBits {
         debug!("handle_async_untyped_retype: Untyped Retype: Invalid object size. {} : {}", user_obj_size, obj_size);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_range_error(item, 0, seL4_MaxUntypedBits);
         return;
     }
     let status = check_object_type(new_type, user_obj_size);
     if status != exception_t::EXCEPTION_NONE {
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
         return;
     }
     let mut node_cap = cap_t::default();
     let status = get_target_cnode(root_cptr, tcb, node_index, node_depth, &mut node_cap);
     if status != exception_t::EXCEPTION_NONE {
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
         return;
     }
 
     let status = check_cnode_slot(&node_cap, node_offset, node_window);
     if status != exception_t::EXCEPTION_NONE {
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
         return;
     }
     let status = service_slot.ensure_no_children();
@@ -174,14 +428,18 @@ fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
     if (untyped_free_bytes >> obj_size) < node_window {
         debug!("handle_async_untyped_retype: Untyped Retype: Insufficient memory({} * {} bytes needed, {} bytes available)", node_window,
                 if obj_size >=  wordBits { -1 } else { 1i64 << obj_size }, untyped_free_bytes);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        unsafe {
+            current_syscall_error._type = seL4_NotEnoughMemory;
+            current_syscall_error.memoryLeft = untyped_free_bytes;
+        }
+        set_async_syscall_error(item);
         return;
     }
 
     let device_mem = service_cap.get_untyped_is_device() != 0;
     if device_mem && !new_type.is_arch_type() && new_type != ObjectType::UnytpedObject {
         debug!("handle_async_untyped_retype: Untyped Retype: Creating kernel objects with device untyped");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_invalid_argument(item, 1);
         return;
     }
     let aligned_free_ref = alignUp(free_ref, obj_size);
@@ -194,8 +452,170 @@ fn handle_async_untyped_retype(item: &mut IPCItem, tcb: &mut tcb_t) {
     if status == exception_t::EXCEPTION_NONE {
         item.extend_msg[0] = AsyncErrorLabel::NoError.into();
     } else {
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
+    }
+}
+
+// 批量UntypedRetype：extend_msg[0]为untyped的CPtr，[1]为组数（1~ASYNC_RETYPE_BATCH_MAX），第i组占3个字：
+// [2+3i] = 类型 | 大小 << 16 | 数量 << 32，[3+3i] = 目标CNode cap的CPtr（深度为0），[4+3i] = 目标CNode中的偏移
+// 所有组都检查通过后才创建对象，共用一次ensure_no_children检查和一次free index更新
+// 响应：失败时[0..]为第一个失败组的错误，与UntypedRetype相同，任何一组都不会被创建；
+// [ASYNC_RETYPE_STATUS_OFFSET + i]为第i组的检查结果（seL4错误类型，seL4_NoError表示通过）
+// 数量在高32位，V1布局的item不能使用
+const ASYNC_RETYPE_BATCH_MAX: usize = 4;
+const ASYNC_RETYPE_STATUS_OFFSET: usize = MAX_IPC_MSG_LEN - ASYNC_RETYPE_BATCH_MAX;
+
+#[derive(Clone, Copy)]
+struct AsyncRetypeRequest {
+    new_type: usize,
+    user_size: usize,
+    count: usize,
+    dest_cptr: usize,
+    dest_offset: usize,
+}
+
+fn handle_async_untyped_retype_batch(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let service_cptr = item.extend_msg[0] as usize;
+    let count = item.extend_msg[1] as usize;
+    let service_lu_ret = tcb.lookup_slot(service_cptr);
+    if unlikely(service_lu_ret.status != exception_t::EXCEPTION_NONE) {
+        debug!("handle_async_untyped_retype_batch: Invocation of invalid service cap {:#x}.", service_cptr);
+        set_async_failed_lookup(item, false);
+        return;
+    }
+    let service_slot: &mut cte_t = unsafe { &mut *service_lu_ret.slot };
+    if service_slot.cap.get_cap_type() != CapTag::CapUntypedCap {
+        debug!("handle_async_untyped_retype_batch: cap {:#x} is not an untyped cap.", service_cptr);
+        set_async_error(item, seL4_IllegalOperation);
+        return;
+    }
+    if count < 1 || count > ASYNC_RETYPE_BATCH_MAX {
+        debug!("handle_async_untyped_retype_batch: invalid number of entries {}.", count);
+        set_async_range_error(item, 1, ASYNC_RETYPE_BATCH_MAX);
+        return;
+    }
+    // 写入响应前先取出所有请求
+    let requests: Vec<AsyncRetypeRequest> = (0..count).map(|i| {
+        let packed = item.extend_msg[2 + 3 * i];
+        AsyncRetypeRequest {
+            new_type: (packed & 0xffff) as usize,
+            user_size: ((packed >> 16) & 0xffff) as usize,
+            count: (packed >> 32) as usize,
+            dest_cptr: item.extend_msg[3 + 3 * i] as usize,
+            dest_offset: item.extend_msg[4 + 3 * i] as usize,
+        }
+    }).collect();
+
+    let service_cap = service_slot.cap;
+    let (free_index, reset) = if service_slot.ensure_no_children() != exception_t::EXCEPTION_NONE {
+        // 原始 untype 有子节点
+        (service_cap.get_untyped_free_index(), false)
+    } else {
+        (0, true)
+    };
+    let region_end = service_cap.get_untyped_ptr() + BIT!(service_cap.get_untyped_block_size());
+    let mut free_ref = GET_FREE_REF(service_cap.get_untyped_ptr(), free_index);
+    let device_mem = service_cap.get_untyped_is_device() != 0;
+
+    let mut entries: Vec<RetypeBatchEntry> = Vec::with_capacity(count);
+    let mut failed = false;
+    for (i, request) in requests.iter().enumerate() {
+        let status = match check_async_retype_entry(request, tcb, &entries, &mut free_ref, region_end, device_mem) {
+            Ok(entry) => {
+                entries.push(entry);
+                seL4_NoError
+            }
+            Err(()) => {
+                debug!("handle_async_untyped_retype_batch: entry {} failed.", i);
+                if !failed {
+                    failed = true;
+                    set_async_syscall_error(item);
+                }
+                unsafe { current_syscall_error._type }
+            }
+        };
+        item.extend_msg[ASYNC_RETYPE_STATUS_OFFSET + i] = status as u64;
+    }
+    if failed {
+        return;
+    }
+    let status = invoke_untyped_retype_batch(service_slot, reset, &entries, free_ref, device_mem as usize);
+    if status == exception_t::EXCEPTION_NONE {
+        item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+    } else {
+        set_async_syscall_error(item);
+    }
+}
+
+// 检查批量retype中的一组，与handle_async_untyped_retype的检查相同，
+// 另外检查与之前的组在同一CNode中的槽是否重叠，并从free_ref开始依次分配内存
+fn check_async_retype_entry(request: &AsyncRetypeRequest, tcb: &mut tcb_t, accepted: &[RetypeBatchEntry],
+                            free_ref: &mut usize, region_end: usize, device_mem: bool) -> Result<RetypeBatchEntry, ()> {
+    let new_type = match ObjectType::from_usize(request.new_type) {
+        Some(new_type) => new_type,
+        None => {
+            debug!("check_async_retype_entry: Invalid object type. {}", request.new_type);
+            unsafe {
+                current_syscall_error._type = seL4_InvalidArgument;
+                current_syscall_error.invalidArgumentNumber = 0;
+            }
+            return Err(());
+        }
+    };
+    let obj_size = new_type.get_object_size(request.user_size);
+    if request.user_size >= wordBits || obj_size > seL4_MaxUntypedBits {
+        debug!("check_async_retype_entry: Invalid object size. {} : {}", request.user_size, obj_size);
+        unsafe {
+            current_syscall_error._type = seL4_RangeError;
+            current_syscall_error.rangeErrorMin = 0;
+            current_syscall_error.rangeErrorMax = seL4_MaxUntypedBits;
+        }
+        return Err(());
+    }
+    if check_object_type(new_type, request.user_size) != exception_t::EXCEPTION_NONE {
+        return Err(());
+    }
+    let mut node_cap = cap_t::default();
+    if get_target_cnode(request.dest_cptr, tcb, 0, 0, &mut node_cap) != exception_t::EXCEPTION_NONE {
+        return Err(());
+    }
+    if check_cnode_slot(&node_cap, request.dest_offset, request.count) != exception_t::EXCEPTION_NONE {
+        return Err(());
+    }
+    let dest_cnode = node_cap.get_cnode_ptr();
+    if accepted.iter().any(|entry| entry.dest_cnode == dest_cnode && request.dest_offset < entry.dest_offset + entry.dest_length
+        && entry.dest_offset < request.dest_offset + request.count) {
+        debug!("check_async_retype_entry: destination window overlaps a previous entry.");
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+        return Err(());
+    }
+    if device_mem && !new_type.is_arch_type() && new_type != ObjectType::UnytpedObject {
+        debug!("check_async_retype_entry: Creating kernel objects with device untyped");
+        unsafe {
+            current_syscall_error._type = seL4_InvalidArgument;
+            current_syscall_error.invalidArgumentNumber = 1;
+        }
+        return Err(());
+    }
+    let retype_base = alignUp(*free_ref, obj_size);
+    if retype_base > region_end || ((region_end - retype_base) >> obj_size) < request.count {
+        debug!("check_async_retype_entry: Insufficient memory({} objects of size bits {} needed, {} bytes available)", request.count,
+            obj_size, region_end.saturating_sub(*free_ref));
+        unsafe {
+            current_syscall_error._type = seL4_NotEnoughMemory;
+            current_syscall_error.memoryLeft = region_end.saturating_sub(*free_ref);
+        }
+        return Err(());
     }
+    *free_ref = retype_base + (request.count << obj_size);
+    Ok(RetypeBatchEntry {
+        new_type,
+        user_size: request.user_size,
+        dest_cnode,
+        dest_offset: request.dest_offset,
+        dest_length: request.count,
+        retype_base,
+    })
 }
 
 fn get_target_cnode(root_cptr: usize, tcb: &mut tcb_t, node_index: usize, node_depth: usize, node_cap: &mut cap_t) -> exception_t {
@@ -204,6 +624,10 @@ fn get_target_cnode(root_cptr: usize, tcb: &mut tcb_t, node_index: usize, node_d
     let root_lu_ret = tcb.lookup_slot(root_cptr);
     if unlikely(root_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("get_target_cnode: Invocation of invalid root cap {:#x}.", root_cptr);
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 0;
+        }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
     let root_slot: &mut cte_t = unsafe {&mut *root_lu_ret.slot };
@@ -222,6 +646,11 @@ fn get_target_cnode(root_cptr: usize, tcb: &mut tcb_t, node_index: usize, node_d
 
     if target_node_cap.get_cap_type() != CapTag::CapCNodeCap {
         debug!("get_target_cnode: Untyped Retype: Destination cap invalid or read-only.");
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 0;
+            current_lookup_fault = lookup_fault_missing_capability_new(node_depth);
+        }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
     *node_cap = target_node_cap;
@@ -233,17 +662,14 @@ fn handle_async_page_get_address(item: &mut IPCItem, tcb: &mut tcb_t) {
     let lu_ret = tcb.lookup_slot(cptr);
     if unlikely(lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_get_address: Invocation of invalid cap {:#x}.", cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let slot: &mut cte_t = unsafe {&mut *lu_ret.slot };
     let vbase_ptr = slot.cap.get_frame_base_ptr();
     let paddr = pptr_to_paddr(vbase_ptr);
     item.extend_msg[0] = AsyncErrorLabel::NoError.into();
-    item.extend_msg[1] = (paddr >> 48) as u16;
-    item.extend_msg[2] = (paddr >> 32) as u16;
-    item.extend_msg[3] = (paddr >> 16) as u16;
-    item.extend_msg[4] = paddr as u16;     
+    item.extend_msg[1] = paddr as u64;
 }
 
 fn handle_async_putchar(item: &mut IPCItem, tcb: &mut tcb_t) {
@@ -267,7 +693,7 @@ fn handle_async_tcb_bind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     let target_tcb_lu_ret = tcb.lookup_slot(target_tcb_cptr);
     if unlikely(target_tcb_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_bind_notification: Invocation of invalid tcb cap {:#x}.", target_tcb_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let target_tcb_slot: &mut cte_t = unsafe {&mut *target_tcb_lu_ret.slot };
@@ -275,14 +701,14 @@ fn handle_async_tcb_bind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     let target_tcb = convert_to_mut_type_ref::<tcb_t>(target_tcb_slot.cap.get_tcb_ptr());
     if target_tcb.tcbBoundNotification != 0 {
         debug!("TCB BindNotification: TCB already has a bound notification.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_IllegalOperation);
         return;
     }
     // 根据Notification的CPtr获取slot
     let ntfn_lu_ret = tcb.lookup_slot(ntfn_cptr);
     if unlikely(ntfn_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_bind_notification: Invocation of invalid Ntfn cap {:#x}.", ntfn_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let ntfn_slot: &mut cte_t = unsafe {&mut *ntfn_lu_ret.slot };
@@ -290,19 +716,19 @@ fn handle_async_tcb_bind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     let ntfn_cap = ntfn_slot.cap;
     if ntfn_cap.get_cap_type() != CapTag::CapNotificationCap {
         debug!("handle_async_bind_notification: TCB BindNotification: Notification is invalid.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_IllegalOperation);
         return;
     }
     // 通过Notification的Capability获取Notification指针得到引用
     let ntfn = convert_to_mut_type_ref::<notification_t>(ntfn_cap.get_nf_ptr());
     if ntfn_cap.get_nf_can_receive() == 0 {
         debug!("handle_async_bind_notification: TCB BindNotification: Insufficient access rights");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_IllegalOperation);
         return;
     }
     if ntfn.get_queue_head() != 0 || ntfn.get_queue_tail() != 0 {
         debug!("handle_async_bind_notification: TCB BindNotification: Notification cannot be bound.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_IllegalOperation);
         return;
     }
     debug!("handle_async_unbind_notification: Before Bind");
@@ -326,7 +752,7 @@ fn handle_async_tcb_unbind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     let lu_ret = tcb.lookup_slot(target_tcb_cptr);
     if unlikely(lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_unbind_notification: Invocation of invalid cap {:#x}.", target_tcb_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let slot: &mut cte_t = unsafe {&mut *lu_ret.slot };
@@ -335,7 +761,7 @@ fn handle_async_tcb_unbind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     // 解除绑定
     if target_tcb.tcbBoundNotification == 0 {
         debug!("handle_async_unbind_notification: TCB BindNotification: TCB already has no bound Notification.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_IllegalOperation);
         return;
     }
     let ntfn_addr = target_tcb.tcbBoundNotification;
@@ -355,6 +781,500 @@ fn handle_async_tcb_unbind_notification(item: &mut IPCItem, tcb: &mut tcb_t) {
     item.extend_msg[0] = AsyncErrorLabel::NoError.into();
 }
 
+#[inline]
+fn set_async_status(item: &mut IPCItem, status: exception_t) {
+    if status == exception_t::EXCEPTION_NONE {
+        item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+    } else {
+        set_async_syscall_error(item);
+    }
+}
+
+// 将current_syscall_error写入响应，布局与同步路径的消息寄存器一致（见set_mrs_for_syscall_error）：
+// extend_msg[0]: SyscallError
+// extend_msg[1]: 错误类型，即同步路径msgInfo中的label（seL4_InvalidArgument等）
+// extend_msg[2..]: 同步路径中的MR0、MR1...
+// 参数和cap的编号与对应的同步调用一致
+fn set_async_syscall_error(item: &mut IPCItem) {
+    let error = unsafe { current_syscall_error };
+    item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+    item.extend_msg[1] = error._type as u64;
+    match error._type {
+        seL4_InvalidArgument => item.extend_msg[2] = error.invalidArgumentNumber as u64,
+        seL4_InvalidCapability => item.extend_msg[2] = error.invalidCapNumber as u64,
+        seL4_RangeError => {
+            item.extend_msg[2] = error.rangeErrorMin as u64;
+            item.extend_msg[3] = error.rangeErrorMax as u64;
+        }
+        seL4_FailedLookup => {
+            item.extend_msg[2] = error.failedLookupWasSource as u64;
+            let fault = unsafe { current_lookup_fault };
+            item.extend_msg[3] = (fault.get_type() + 1) as u64;
+            match fault.get_lookup_fault_type() {
+                LookupFaultType::InvaildRoot => {}
+                LookupFaultType::MissingCap => {
+                    item.extend_msg[4] = fault.missing_cap_get_bits_left() as u64;
+                }
+                LookupFaultType::DepthMismatch => {
+                    item.extend_msg[4] = fault.depth_mismatch_get_bits_left() as u64;
+                    item.extend_msg[5] = fault.depth_mismatch_get_bits_found() as u64;
+                }
+                LookupFaultType::GuardMismatch => {
+                    item.extend_msg[4] = fault.guard_mismatch_get_bits_left() as u64;
+                    item.extend_msg[5] = fault.guard_mismatch_get_guard_found() as u64;
+                    item.extend_msg[6] = fault.guard_mismatch_get_bits_found() as u64;
+                }
+            }
+        }
+        seL4_NotEnoughMemory => item.extend_msg[2] = error.memoryLeft as u64,
+        _ => {}
+    }
+}
+
+// 无附加参数的错误：seL4_IllegalOperation、seL4_AlignmentError、seL4_DeleteFirst、seL4_RevokeFirst等
+pub fn set_async_error(item: &mut IPCItem, error_type: usize) {
+    unsafe { current_syscall_error._type = error_type; }
+    set_async_syscall_error(item);
+}
+
+fn set_async_invalid_argument(item: &mut IPCItem, number: usize) {
+    unsafe {
+        current_syscall_error._type = seL4_InvalidArgument;
+        current_syscall_error.invalidArgumentNumber = number;
+    }
+    set_async_syscall_error(item);
+}
+
+pub fn set_async_invalid_capability(item: &mut IPCItem, number: usize) {
+    unsafe {
+        current_syscall_error._type = seL4_InvalidCapability;
+        current_syscall_error.invalidCapNumber = number;
+    }
+    set_async_syscall_error(item);
+}
+
+fn set_async_range_error(item: &mut IPCItem, min: usize, max: usize) {
+    unsafe {
+        current_syscall_error._type = seL4_RangeError;
+        current_syscall_error.rangeErrorMin = min;
+        current_syscall_error.rangeErrorMax = max;
+    }
+    set_async_syscall_error(item);
+}
+
+// current_lookup_fault由失败的查找（resolve_address_bits等）设置
+fn set_async_failed_lookup(item: &mut IPCItem, is_source: bool) {
+    unsafe {
+        current_syscall_error._type = seL4_FailedLookup;
+        current_syscall_error.failedLookupWasSource = is_source as usize;
+    }
+    set_async_syscall_error(item);
+}
+
+// 查找失败或cap不是TCB时设置current_syscall_error，cap_number为该cap在同步调用中的编号
+fn lookup_async_tcb(tcb: &mut tcb_t, cptr: usize, cap_number: usize) -> Option<(&'static mut cte_t, &'static mut tcb_t)> {
+    let lu_ret = tcb.lookup_slot(cptr);
+    if unlikely(lu_ret.status != exception_t::EXCEPTION_NONE) {
+        debug!("lookup_async_tcb: Invocation of invalid tcb cap {:#x}.", cptr);
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 0;
+        }
+        return None;
+    }
+    let slot: &mut cte_t = unsafe { &mut *lu_ret.slot };
+    if slot.cap.get_cap_type() != CapTag::CapThreadCap {
+        debug!("lookup_async_tcb: cap {:#x} is not a tcb cap.", cptr);
+        unsafe {
+            current_syscall_error._type = seL4_InvalidCapability;
+            current_syscall_error.invalidCapNumber = cap_number;
+        }
+        return None;
+    }
+    let target = convert_to_mut_type_ref::<tcb_t>(slot.cap.get_tcb_ptr());
+    #[cfg(feature = "ENABLE_SMP")]
+    unsafe { crate::deps::remoteTCBStall(target); }
+    Some((slot, target))
+}
+
+fn lookup_async_slot(tcb: &mut tcb_t, cptr: usize) -> Option<&'static mut cte_t> {
+    let lu_ret = tcb.lookup_slot(cptr);
+    if unlikely(lu_ret.status != exception_t::EXCEPTION_NONE) {
+        debug!("lookup_async_slot: Invocation of invalid cap {:#x}.", cptr);
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 0;
+        }
+        return None;
+    }
+    Some(unsafe { &mut *lu_ret.slot })
+}
+
+// 寄存器的值直接放在item中传递，不经过提交线程的IPC buffer（请求执行时IPC buffer可能已被用于其他调用）。
+// 寄存器编号i < n_frameRegisters对应frameRegisters[i]，否则对应gpRegisters[i - n_frameRegisters]。
+// 一个item放不下的寄存器由用户态拆成多个请求，每个请求从extend_msg[3]指定的编号开始。
+// ReadRegisters请求：extend_msg[0]为目标TCB的CPtr，[1]为flags，[2]为个数n，[3]为起始编号
+// 响应：[1]为读取的个数，[ASYNC_READ_REGS_OFFSET..]为寄存器的值
+// WriteRegisters请求：[0..4]同上，[ASYNC_WRITE_REGS_OFFSET..]为待写入的值；flags的bit 0表示写入后重启目标线程
+// 寄存器的值超过16位，V1布局的item不能使用
+const ASYNC_READ_REGS_OFFSET: usize = 2;
+const ASYNC_READ_REGS_MAX: usize = MAX_IPC_MSG_LEN - ASYNC_READ_REGS_OFFSET;
+const ASYNC_WRITE_REGS_OFFSET: usize = 4;
+const ASYNC_WRITE_REGS_MAX: usize = MAX_IPC_MSG_LEN - ASYNC_WRITE_REGS_OFFSET;
+
+#[inline]
+fn async_register_of(index: usize) -> usize {
+    if index < n_frameRegisters {
+        frameRegisters[index]
+    } else {
+        gpRegisters[index - n_frameRegisters]
+    }
+}
+
+fn handle_async_tcb_read_registers(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let flags = item.extend_msg[1] as usize;
+    let n = item.extend_msg[2] as usize;
+    let first = item.extend_msg[3] as usize;
+    if n < 1 || n > ASYNC_READ_REGS_MAX {
+        debug!("handle_async_tcb_read_registers: Attempted to read an invalid number of registers:{}", n);
+        set_async_range_error(item, 1, ASYNC_READ_REGS_MAX);
+        return;
+    }
+    if first >= n_frameRegisters + n_gpRegisters || n > n_frameRegisters + n_gpRegisters - first {
+        debug!("handle_async_tcb_read_registers: registers {}..{} out of range", first, first + n);
+        set_async_range_error(item, 0, n_frameRegisters + n_gpRegisters - n);
+        return;
+    }
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    if target.get_ptr() == tcb.get_ptr() {
+        debug!("handle_async_tcb_read_registers: Attempted to read our own registers.");
+        set_async_error(item, seL4_IllegalOperation);
+        return;
+    }
+    if flags & BIT!(ReadRegisters_suspend) != 0 {
+        target.cancel_ipc();
+        target.suspend();
+    }
+    for i in 0..n {
+        item.extend_msg[ASYNC_READ_REGS_OFFSET + i] = target.get_register(async_register_of(first + i)) as u64;
+    }
+    item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+    item.extend_msg[1] = n as u64;
+}
+
+fn handle_async_tcb_write_registers(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let flags = item.extend_msg[1] as usize;
+    let w = item.extend_msg[2] as usize;
+    let first = item.extend_msg[3] as usize;
+    if w > ASYNC_WRITE_REGS_MAX {
+        debug!("handle_async_tcb_write_registers: Attempted to write an invalid number of registers:{}", w);
+        set_async_range_error(item, 0, ASYNC_WRITE_REGS_MAX);
+        return;
+    }
+    if first > n_frameRegisters + n_gpRegisters || w > n_frameRegisters + n_gpRegisters - first {
+        debug!("handle_async_tcb_write_registers: registers {}..{} out of range", first, first + w);
+        set_async_range_error(item, 0, n_frameRegisters + n_gpRegisters - w);
+        return;
+    }
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    if target.get_ptr() == tcb.get_ptr() {
+        debug!("handle_async_tcb_write_registers: Attempted to write our own registers.");
+        set_async_error(item, seL4_IllegalOperation);
+        return;
+    }
+    for i in 0..w {
+        target.set_register(async_register_of(first + i), item.extend_msg[ASYNC_WRITE_REGS_OFFSET + i] as usize);
+    }
+    target.set_register(NextIP, target.get_register(FaultIP));
+    if flags & BIT!(0) != 0 {
+        target.cancel_ipc();
+        target.restart();
+    }
+    if target.is_current() {
+        rescheduleRequired();
+    }
+    item.extend_msg[0] = AsyncErrorLabel::NoError.into();
+}
+
+fn handle_async_tcb_copy_registers(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let flags = item.extend_msg[2] as usize;
+    let (_, dest) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (_, src) = match lookup_async_tcb(tcb, item.extend_msg[1] as usize, 1) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_copy_registers(dest, src,
+        flags & BIT!(CopyRegisters_suspendSource), flags & BIT!(CopyRegisters_resumeTarget),
+        flags & BIT!(CopyRegisters_transferFrame), flags & BIT!(CopyRegisters_transferInteger), 0);
+    set_async_status(item, status);
+}
+
+fn handle_async_tcb_suspend_resume(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncMessageLabel) {
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = match label {
+        AsyncMessageLabel::TCBSuspend => invoke_tcb_suspend(target),
+        AsyncMessageLabel::TCBResume => invoke_tcb_resume(target),
+        _ => exception_t::EXCEPTION_SYSCALL_ERROR
+    };
+    set_async_status(item, status);
+}
+
+fn handle_async_tcb_set_sched(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncMessageLabel) {
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (_, auth_tcb) = match lookup_async_tcb(tcb, item.extend_msg[1] as usize, 1) {
+        Some(ret) => ret,
+        None => {
+            debug!("handle_async_tcb_set_sched: authority cap not a TCB.");
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = match label {
+        AsyncMessageLabel::TCBSetPriority => {
+            let new_prio = item.extend_msg[2] as usize;
+            if check_prio(new_prio, auth_tcb) != exception_t::EXCEPTION_NONE {
+                debug!("handle_async_tcb_set_sched: Requested priority {} too high (max {}).", new_prio, auth_tcb.tcbMCP);
+                set_async_syscall_error(item);
+                return;
+            }
+            invoke_tcb_set_priority(target, new_prio)
+        }
+        AsyncMessageLabel::TCBSetMCPriority => {
+            let new_mcp = item.extend_msg[2] as usize;
+            if check_prio(new_mcp, auth_tcb) != exception_t::EXCEPTION_NONE {
+                debug!("handle_async_tcb_set_sched: Requested maximum controlled priority {} too high (max {}).", new_mcp, auth_tcb.tcbMCP);
+                set_async_syscall_error(item);
+                return;
+            }
+            invoke_tcb_set_mcp(target, new_mcp)
+        }
+        AsyncMessageLabel::TCBSetSchedParams => {
+            let new_mcp = item.extend_msg[2] as usize;
+            let new_prio = item.extend_msg[3] as usize;
+            if check_prio(new_mcp, auth_tcb) != exception_t::EXCEPTION_NONE
+                || check_prio(new_prio, auth_tcb) != exception_t::EXCEPTION_NONE {
+                debug!("handle_async_tcb_set_sched: Requested mcp {} or priority {} too high (max {}).", new_mcp, new_prio, auth_tcb.tcbMCP);
+                set_async_syscall_error(item);
+                return;
+            }
+            invoke_tcb_set_mcp(target, new_mcp);
+            invoke_tcb_set_priority(target, new_prio)
+        }
+        _ => exception_t::EXCEPTION_SYSCALL_ERROR
+    };
+    set_async_status(item, status);
+}
+
+/// 解析新的IPC buffer，地址以 seL4_IPCBufferSizeBits 对齐后的值传入
+fn get_async_ipc_buffer(tcb: &mut tcb_t, addr: usize, frame_cptr: usize) -> Result<(Option<&'static mut cte_t>, cap_t), exception_t> {
+    if addr == 0 {
+        return Ok((None, cap_t::new_null_cap()));
+    }
+    let slot = match lookup_async_slot(tcb, frame_cptr) {
+        Some(ret) => ret,
+        None => {
+            return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+        }
+    };
+    let cap = slot.cap;
+    let dc_ret = slot.derive_cap(&cap);
+    if dc_ret.status != exception_t::EXCEPTION_NONE {
+        return Err(dc_ret.status);
+    }
+    let status = check_ipc_buffer_vaild(addr, &dc_ret.cap);
+    if status != exception_t::EXCEPTION_NONE {
+        return Err(status);
+    }
+    Ok((Some(slot), dc_ret.cap))
+}
+
+/// 解析新的CSpace与VSpace根，和 decode_set_space 的检查一致
+fn get_async_space(tcb: &mut tcb_t, target: &tcb_t, croot_cptr: usize, croot_data: usize, vroot_cptr: usize, vroot_data: usize)
+    -> Result<(&'static mut cte_t, cap_t, &'static mut cte_t, cap_t), exception_t> {
+    if target.get_cspace(tcbCTable).is_long_running_delete()
+        || target.get_cspace(tcbVTable).is_long_running_delete() {
+        debug!("get_async_space: CSpace or VSpace currently being deleted.");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+    }
+    let croot_slot = match lookup_async_slot(tcb, croot_cptr) {
+        Some(ret) => ret,
+        None => {
+            return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+        }
+    };
+    let croot_cap = decode_set_space_args(croot_data, croot_slot.cap, croot_slot)?;
+    if croot_cap.get_cap_type() != CapTag::CapCNodeCap {
+        debug!("get_async_space: CSpace cap is invalid.");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+    }
+    let vroot_slot = match lookup_async_slot(tcb, vroot_cptr) {
+        Some(ret) => ret,
+        None => {
+            return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+        }
+    };
+    let vroot_cap = decode_set_space_args(vroot_data, vroot_slot.cap, vroot_slot)?;
+    if !is_valid_vtable_root(&vroot_cap) {
+        debug!("get_async_space: VSpace cap is invalid.");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+    }
+    Ok((croot_slot, croot_cap, vroot_slot, vroot_cap))
+}
+
+fn handle_async_tcb_configure(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let fault_ep = item.extend_msg[1] as usize;
+    let buffer_addr = item.extend_msg[6] as usize;
+    let (target_slot, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (buffer_slot, buffer_cap) = match get_async_ipc_buffer(tcb, buffer_addr, item.extend_msg[7] as usize) {
+        Ok(ret) => ret,
+        Err(_) => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (croot_slot, croot_cap, vroot_slot, vroot_cap) = match get_async_space(tcb, target,
+        item.extend_msg[2] as usize, item.extend_msg[3] as usize, item.extend_msg[4] as usize, item.extend_msg[5] as usize) {
+        Ok(ret) => ret,
+        Err(_) => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_set_space(target, target_slot, fault_ep, croot_cap, croot_slot, vroot_cap, vroot_slot);
+    if status != exception_t::EXCEPTION_NONE {
+        set_async_status(item, status);
+        return;
+    }
+    let status = invoke_tcb_set_ipc_buffer(target, target_slot, buffer_addr, buffer_cap, buffer_slot);
+    set_async_status(item, status);
+}
+
+fn handle_async_tcb_set_ipc_buffer(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let buffer_addr = item.extend_msg[1] as usize;
+    let (target_slot, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (buffer_slot, buffer_cap) = match get_async_ipc_buffer(tcb, buffer_addr, item.extend_msg[2] as usize) {
+        Ok(ret) => ret,
+        Err(_) => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_set_ipc_buffer(target, target_slot, buffer_addr, buffer_cap, buffer_slot);
+    set_async_status(item, status);
+}
+
+fn handle_async_tcb_set_space(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let fault_ep = item.extend_msg[1] as usize;
+    let (target_slot, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let (croot_slot, croot_cap, vroot_slot, vroot_cap) = match get_async_space(tcb, target,
+        item.extend_msg[2] as usize, item.extend_msg[3] as usize, item.extend_msg[4] as usize, item.extend_msg[5] as usize) {
+        Ok(ret) => ret,
+        Err(_) => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_set_space(target, target_slot, fault_ep, croot_cap, croot_slot, vroot_cap, vroot_slot);
+    set_async_status(item, status);
+}
+
+#[cfg(feature = "ENABLE_SMP")]
+fn handle_async_tcb_set_affinity(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let affinity = item.extend_msg[1] as usize;
+    if affinity >= CONFIG_MAX_NUM_NODES {
+        debug!("handle_async_tcb_set_affinity: Requested CPU does not exist.");
+        set_async_error(item, seL4_IllegalOperation);
+        return;
+    }
+    if async_polling_on(affinity) {
+        debug!("handle_async_tcb_set_affinity: Requested CPU is reserved for async syscall polling.");
+        set_async_error(item, seL4_IllegalOperation);
+        return;
+    }
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_set_affinity(target, affinity);
+    set_async_status(item, status);
+}
+
+#[cfg(not(feature = "ENABLE_SMP"))]
+fn handle_async_tcb_set_affinity(item: &mut IPCItem, _tcb: &mut tcb_t) {
+    debug!("handle_async_tcb_set_affinity: Illegal operation without SMP.");
+    set_async_error(item, seL4_IllegalOperation);
+}
+
+fn handle_async_tcb_set_tls_base(item: &mut IPCItem, tcb: &mut tcb_t) {
+    let base = item.extend_msg[1] as usize;
+    let (_, target) = match lookup_async_tcb(tcb, item.extend_msg[0] as usize, 0) {
+        Some(ret) => ret,
+        None => {
+            set_async_syscall_error(item);
+            return;
+        }
+    };
+    let status = invoke_tcb_set_tls_base(target, base);
+    set_async_status(item, status);
+}
+
 fn handle_async_cnode_syscall(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncMessageLabel) {
     let label = AsyncMessageLabel::from(item.msg_info);
     // 根据dest_root_cptr获取dest_root_cap
@@ -364,7 +1284,7 @@ fn handle_async_cnode_syscall(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncM
     let dest_root_lu_ret = tcb.lookup_slot(dest_root_cptr);
     if unlikely(dest_root_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_cnode_syscall: Invocation of invalid cap {:#x}.", dest_root_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let dest_root_slot = unsafe {&mut *dest_root_lu_ret.slot };
@@ -373,13 +1293,15 @@ fn handle_async_cnode_syscall(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncM
     let dest_slot_lu_ret = lookup_slot_for_cnode_op(false, &dest_root_cap, dest_index, dest_depth);
     if dest_slot_lu_ret.status != exception_t::EXCEPTION_NONE {
         debug!("handle_async_cnode_copy: CNode operation: Dest Target slot invalid.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
         return;
     }
     let dest_slot = convert_to_mut_type_ref::<cte_t>(dest_slot_lu_ret.slot as usize);
     let error = match label {
-        AsyncMessageLabel::CNodeCopy | AsyncMessageLabel::CNodeMint => handle_async_cnode_syscall_with_two_slot(item, tcb, dest_slot, label),
+        AsyncMessageLabel::CNodeCopy | AsyncMessageLabel::CNodeMint | AsyncMessageLabel::CNodeMove | AsyncMessageLabel::CNodeMutate =>
+            handle_async_cnode_syscall_with_two_slot(item, tcb, dest_slot, label),
         AsyncMessageLabel::CNodeDelete => handle_async_cnode_delete(dest_slot),
+        AsyncMessageLabel::CNodeSaveCaller => handle_async_cnode_save_caller(dest_slot, tcb),
         AsyncMessageLabel::CNodeCancelBadgedSends => invoke_cnode_cancel_badged_sends(dest_slot),
         AsyncMessageLabel::CNodeRevoke => invoke_cnode_revoke(dest_slot),
         _ => exception_t::EXCEPTION_SYSCALL_ERROR
@@ -387,13 +1309,14 @@ fn handle_async_cnode_syscall(item: &mut IPCItem, tcb: &mut tcb_t, label: AsyncM
     if error == exception_t::EXCEPTION_NONE {
         item.extend_msg[0] = AsyncErrorLabel::NoError.into();
     } else {
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
     }
 }
 
 fn handle_async_cnode_syscall_with_two_slot(item: &mut IPCItem, tcb: &mut tcb_t, dest_slot: &mut cte_t, label: AsyncMessageLabel) -> exception_t{
     if dest_slot.cap.get_cap_type() != CapTag::CapNullCap {
-        debug!("handle_async_cnode_syscall_with_two_slot: CNode Copy: Destination not empty.");
+        debug!("handle_async_cnode_syscall_with_two_slot: CNode Copy/Mint/Move/Mutate: Destination not empty.");
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
     // src有关参数
@@ -404,6 +1327,10 @@ fn handle_async_cnode_syscall_with_two_slot(item: &mut IPCItem, tcb: &mut tcb_t,
     let src_root_lu_ret = tcb.lookup_slot(src_root_cptr);
     if unlikely(src_root_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_cnode_syscall_with_two_slot: Invocation of invalid src root cap {:#x}.", src_root_cptr);
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 1;
+        }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
     let src_root_slot = unsafe {&mut *src_root_lu_ret.slot };
@@ -417,6 +1344,11 @@ fn handle_async_cnode_syscall_with_two_slot(item: &mut IPCItem, tcb: &mut tcb_t,
     let src_slot = convert_to_mut_type_ref::<cte_t>(src_slot_lu_ret.slot as usize);
     if src_slot.cap.get_cap_type() == CapTag::CapNullCap {
         debug!("handle_async_cnode_syscall_with_two_slot: CNode operation: Source empty.");
+        unsafe {
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 1;
+            current_lookup_fault = lookup_fault_missing_capability_new(src_depth);
+        }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
     match label {
@@ -448,6 +1380,27 @@ fn handle_async_cnode_delete(dest_slot: &mut cte_t) -> exception_t{
     dest_slot.delete_all(true)
 }
 
+fn handle_async_cnode_save_caller(dest_slot: &mut cte_t, tcb: &mut tcb_t) -> exception_t {
+    // 与 invoke_cnode_save_caller 一致，但保存的是发起线程而不是当前线程的 caller cap
+    if dest_slot.cap.get_cap_type() != CapTag::CapNullCap {
+        debug!("handle_async_cnode_save_caller: CNode SaveCaller: Destination slot not empty.");
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let src_slot = tcb.get_cspace_mut_ref(tcbCaller);
+    let cap = src_slot.cap;
+    match cap.get_cap_type() {
+        CapTag::CapNullCap => debug!("handle_async_cnode_save_caller: CNode SaveCaller: Reply cap not present."),
+        CapTag::CapReplyCap => {
+            if cap.get_reply_master() == 0 {
+                cte_move(&cap, src_slot, dest_slot);
+            }
+        }
+        _ => panic!("caller capability must be null or reply"),
+    }
+    exception_t::EXCEPTION_NONE
+}
+
 fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
     // service
     let service_cptr = item.extend_msg[0] as usize;
@@ -455,7 +1408,7 @@ fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
     let service_lu_ret = tcb.lookup_slot(service_cptr);
     if unlikely(service_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_table_map: Invocation of invalid service cap {:#x}.", service_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let service_slot: &mut cte_t = unsafe {&mut *service_lu_ret.slot };
@@ -466,7 +1419,7 @@ fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
     let lvl1pt_lu_ret = tcb.lookup_slot(lvl1pt_cptr);
     if unlikely(lvl1pt_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_table_map: Invocation of invalid lvl1pt cap {:#x}.", lvl1pt_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let lvl1pt_slot: &mut cte_t = unsafe {&mut *lvl1pt_lu_ret.slot };
@@ -474,14 +1427,14 @@ fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
 
     if unlikely(service_cap.get_pt_is_mapped() != 0) {
         debug!("handle_async_page_table_map: RISCVPageTable: PageTable is already mapped.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_invalid_capability(item, 0);
         return;
     }
 
-    let vaddr: usize = (item.extend_msg[2] as usize) << 12;
+    let vaddr: usize = item.extend_msg[2] as usize;
     if unlikely(vaddr >= USER_TOP) {
         debug!("handle_async_page_table_map: RISCVPageTableMap: Virtual address cannot be in kernel window.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_invalid_argument(item, 0);
         return;
     }
 
@@ -491,13 +1444,13 @@ fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
         // debug!("lu_ret.ptBitsLeft: {}", lu_ret.ptBitsLeft);
         if lu_ret.ptBitsLeft == seL4_PageBits || lu_slot.get_vaild() != 0 {
             debug!("handle_async_page_table_map: RISCVPageTableMap: All objects mapped at this address");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_error(item, seL4_DeleteFirst);
             return;
         }
         let error = invoke_page_table_map(service_cap, lu_slot, asid, vaddr & !MASK!(lu_ret.ptBitsLeft));
         if error != exception_t::EXCEPTION_NONE {
             debug!("handle_async_page_table_map: invoke error");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_syscall_error(item);
             return;
         } else {
             item.extend_msg[0] = AsyncErrorLabel::NoError.into();
@@ -505,7 +1458,7 @@ fn handle_async_page_table_map(item: &mut IPCItem, tcb: &mut tcb_t) {
         }
     } else {
         debug!("handle_async_page_table_map: RISCVPageTableMap: cannot get vspace.");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
         return;
     }   
 }
@@ -517,14 +1470,14 @@ fn handle_async_page_table_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
     let service_lu_ret = tcb.lookup_slot(service_cptr);
     if unlikely(service_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_table_unmap: Invocation of invalid service cap {:#x}.", service_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let service_slot: &mut cte_t = unsafe {&mut *service_lu_ret.slot };
     // translate
     if !service_slot.is_final_cap() {
         debug!("handle_async_page_table_unmap: RISCVPageTableUnmap: cannot unmap if more than once cap exists");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_error(item, seL4_RevokeFirst);
         return;
     }
     let cap = &mut service_slot.cap;
@@ -534,7 +1487,7 @@ fn handle_async_page_table_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
         let pte_ptr = cap.get_pt_base_ptr() as *mut pte_t;
         if find_ret.status == exception_t::EXCEPTION_NONE && find_ret.vspace_root.unwrap() == pte_ptr {
             debug!("RISCVPageTableUnmap: cannot call unmap on top level PageTable");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_error(item, seL4_IllegalOperation);
             return;
         }
     }
@@ -542,7 +1495,7 @@ fn handle_async_page_table_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
     let error = invoke_page_table_unmap(cap);
     if error != exception_t::EXCEPTION_NONE {
         debug!("handle_async_page_table_unmap: invoke error");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
     } else {
         item.extend_msg[0] = AsyncErrorLabel::NoError.into();
     }
@@ -556,7 +1509,7 @@ fn handle_async_page_map(item: &mut IPCItem, tcb: &mut tcb_t) {
     let frame_lu_ret = tcb.lookup_slot(frame_cptr);
     if unlikely(frame_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_table_map: Invocation of invalid frame cap {:#x}.", frame_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let frame_slot: &mut cte_t = unsafe {&mut *frame_lu_ret.slot };
@@ -566,13 +1519,13 @@ fn handle_async_page_map(item: &mut IPCItem, tcb: &mut tcb_t) {
     let lvl1pt_lu_ret = tcb.lookup_slot(lvl1pt_cptr);
     if unlikely(lvl1pt_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_map: Invocation of invalid lvl1pt cap {:#x}.", lvl1pt_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let lvl1pt_slot: &mut cte_t = unsafe {&mut *lvl1pt_lu_ret.slot };
     let lvl1pt_cap = lvl1pt_slot.cap;
     // 其他
-    let vaddr: usize = (item.extend_msg[2] as usize) << 12;
+    let vaddr: usize = item.extend_msg[2] as usize;
     // debug!("handle_async_page_map: vaddr: {:#x}", vaddr);
     let w_rights_mask = item.extend_msg[3] as usize;
     let attr = vm_attributes_t::from_word(item.extend_msg[4] as usize);
@@ -581,20 +1534,21 @@ fn handle_async_page_map(item: &mut IPCItem, tcb: &mut tcb_t) {
         let vtop = vaddr + BIT!(pageBitsForSize(frame_size)) - 1;
         if unlikely(vtop >= USER_TOP) {
             debug!("handle_async_page_map: vtop is greater than user top");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_invalid_argument(item, 0);
             return;
         }
 
         if unlikely(!checkVPAlignment(frame_size, vaddr)) {
             debug!("handle_async_page_map: frame no align");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_error(item, seL4_AlignmentError);
             return;
         }
 
         let lu_ret = lvl1pt.lookup_pt_slot(vaddr);
         if lu_ret.ptBitsLeft != pageBitsForSize(frame_size) {
             debug!("handle_async_page_map: ptBitLeft != pageBitsForSize");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            unsafe { current_lookup_fault = lookup_fault_missing_capability_new(lu_ret.ptBitsLeft); }
+            set_async_failed_lookup(item, false);
             return;
         }
 
@@ -603,38 +1557,38 @@ fn handle_async_page_map(item: &mut IPCItem, tcb: &mut tcb_t) {
         if frame_asid != asidInvalid {
             if frame_asid != asid {
                 debug!("handle_async_page_map: RISCVPageMap: Attempting to remap a frame that does not belong to the passed address space");
-                item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+                set_async_invalid_capability(item, 1);
                 return;
             }
 
             if frame_slot.cap.get_frame_mapped_address() != vaddr {
                 debug!("handle_async_page_map: RISCVPageMap: attempting to map frame into multiple addresses");
-                item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+                set_async_invalid_argument(item, 0);
                 return;
             }
 
             if pt_slot.is_pte_table() {
                 debug!("handle_async_page_map: RISCVPageMap: no mapping to remap.");
-                item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+                set_async_error(item, seL4_DeleteFirst);
                 return;
             }
         } else {
             if pt_slot.get_vaild() != 0 {
                 debug!("handle_async_page_map: Virtual address already mapped");
-                item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+                set_async_error(item, seL4_DeleteFirst);
                 return;
             }
         }
         let error = invoke_page_map(&mut frame_slot.cap.clone(), w_rights_mask, vaddr, asid, attr, pt_slot, frame_slot);
         if error != exception_t::EXCEPTION_NONE {
             debug!("handle_async_page_map: invoke error");
-            item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+            set_async_syscall_error(item);
         } else {
             item.extend_msg[0] = AsyncErrorLabel::NoError.into();
         }
     } else {
         debug!("handle_async_page_map: cannot get vspace");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
     }
 }
 
@@ -645,7 +1599,7 @@ fn handle_async_page_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
     let service_lu_ret = tcb.lookup_slot(service_cptr);
     if unlikely(service_lu_ret.status != exception_t::EXCEPTION_NONE) {
         debug!("handle_async_page_table_map: Invocation of invalid service cap {:#x}.", service_cptr);
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_failed_lookup(item, false);
         return;
     }
     let service_slot: &mut cte_t = unsafe {&mut *service_lu_ret.slot };
@@ -653,7 +1607,7 @@ fn handle_async_page_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
     let error = invoke_page_unmap(service_slot);
     if error != exception_t::EXCEPTION_NONE {
         debug!("handle_async_page_unmap: invoke error");
-        item.extend_msg[0] = AsyncErrorLabel::SyscallError.into();
+        set_async_syscall_error(item);
     } else {
         item.extend_msg[0] = AsyncErrorLabel::NoError.into();
     }
@@ -663,6 +1617,10 @@ fn handle_async_page_unmap(item: &mut IPCItem, tcb: &mut tcb_t) {
 fn get_vspace(lvl1pt_cap: &cap_t) -> Option<(&mut pte_t, usize)> {
     if lvl1pt_cap.get_cap_type() != CapTag::CapPageTableCap || lvl1pt_cap.get_pt_is_mapped() == asidInvalid {
         debug!("get_vspace: RISCVMMUInvocation: Invalid top-level PageTable.");
+        unsafe {
+            current_syscall_error._type = seL4_InvalidCapability;
+            current_syscall_error.invalidCapNumber = 1;
+        }
         return None;
     }
 
@@ -672,10 +1630,19 @@ fn get_vspace(lvl1pt_cap: &cap_t) -> Option<(&mut pte_t, usize)> {
     let find_ret = find_vspace_for_asid(asid);
     if find_ret.status != exception_t::EXCEPTION_NONE {
         debug!("get_vspace: RISCVMMUInvocation: ASID lookup failed");
+        unsafe {
+            current_lookup_fault = find_ret.lookup_fault.unwrap();
+            current_syscall_error._type = seL4_FailedLookup;
+            current_syscall_error.failedLookupWasSource = 0;
+        }
         return None;
     }
     if find_ret.vspace_root.unwrap() as usize != lvl1pt.get_ptr() {
         debug!("get_vspace: RISCVMMUInvocation: ASID lookup failed");
+        unsafe {
+            current_syscall_error._type = seL4_InvalidCapability;
+            current_syscall_error.invalidCapNumber = 1;
+        }
         return None;
     }
     Some((lvl1pt, asid))
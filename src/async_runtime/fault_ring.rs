@@ -0,0 +1,274 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering::SeqCst;
+use log::debug;
+use crate::async_runtime::{coroutine_spawn_with_owner, coroutine_wake, coroutine_cancel, CoroutineId};
+use crate::async_runtime::new_buffer::NewBuffer;
+use crate::async_runtime::polling::async_polling_active;
+use crate::async_runtime::async_syscall_handler::send_async_syscall_uintr;
+use crate::async_runtime::utils::yield_now;
+use crate::common::fault::*;
+use crate::common::sel4_config::{MessageID_Syscall, MessageID_Exception};
+use crate::common::utils::convert_to_mut_type_ref;
+use crate::cspace::interface::cap_t;
+use crate::kernel::boot::{current_fault, current_lookup_fault};
+use crate::task_manager::{tcb_t, set_thread_state, possible_switch_to, ThreadState, FaultIP, n_syscallMessage, n_exceptionMessage, fault_messages};
+
+// 异步fault投递
+// 线程注册fault ring后，其VM、cap、未知系统调用和用户异常fault不再通过fault IPC发送给tcbFaultHandler，
+// 而是写入与pager共享的fault ring，并通过用户态中断通知pager，fault线程阻塞直到pager通过同一个ring恢复它。
+// fault ring与NewBuffer布局相同，元素为FaultRecord：
+// - recv_req_status：内核协程是否在处理恢复记录，为false时pager需要通过SysWakeSyscallHandler唤醒内核协程
+// - recv_reply_status：pager是否在处理fault记录，为false时内核需要发送用户态中断
+// - req_items：pager写入的恢复记录
+// - res_items：内核写入的fault记录
+// 多个线程可以注册到同一个ring，由注册时指定的badge区分，一个ring只有一个处理恢复记录的协程，
+// 该协程属于ring上某个注册项的pager，该pager的注册全部注销时转给剩余注册项的pager
+
+// fault记录的最大长度（未知系统调用fault的消息长度）
+pub const FAULT_RECORD_WORDS: usize = n_syscallMessage + 1;
+
+// fault记录：label为fault类型，words与fault IPC的消息相同
+// 恢复记录：badge为fault线程，label和words与fault IPC的回复相同，
+// 未知系统调用和用户异常fault的回复label不为0时线程不再恢复
+#[repr(C, align(8))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FaultRecord {
+    pub badge: u64,
+    pub label: u64,
+    pub length: u64,
+    pub words: [u64; FAULT_RECORD_WORDS],
+}
+
+pub struct FaultRingMap {
+    // fault ring的地址
+    pub buf: usize,
+    // 处理恢复记录的协程
+    pub cid: CoroutineId,
+    // 注册fault ring的线程（pager）
+    pub pager: usize,
+    // 注册fault ring的fault线程
+    pub tcb: usize,
+    pub badge: usize,
+    // 用于通知pager的notification
+    pub ntfn: usize,
+    // 内核发送者UIST中的表项，注册失败时为None
+    pub sender_id: Option<usize>,
+}
+
+pub static mut FAULT_RING_MAP: Vec<FaultRingMap> = Vec::new();
+
+// fault ring占用的字节数，承载ring的frame不能小于该值
+pub const FAULT_RING_SIZE: usize = core::mem::size_of::<NewBuffer<FaultRecord>>();
+
+#[inline]
+fn fault_ring_at(ptr: usize) -> &'static mut NewBuffer<FaultRecord> {
+    NewBuffer::<FaultRecord>::from_ptr(ptr)
+}
+
+// 为线程注册fault ring，ring已被其他notification注册、线程已注册或badge在ring中重复时返回Err
+pub fn async_fault_ring_register(ntfn_cap: &cap_t, buf: usize, tcb: &mut tcb_t, badge: usize, pager: &mut tcb_t) -> Result<(), ()> {
+    let maps = unsafe { &mut FAULT_RING_MAP };
+    if maps.iter().any(|map| map.tcb == tcb.get_ptr() || (map.buf == buf && (map.badge == badge || map.ntfn != ntfn_cap.get_nf_ptr()))) {
+        debug!("async_fault_ring_register: thread or badge already registered");
+        return Err(());
+    }
+    // 同一个ring的注册共用处理协程和UIST表项
+    let (cid, sender_id) = match maps.iter().find(|map| map.buf == buf) {
+        Some(map) => (map.cid, map.sender_id),
+        None => {
+            let ring = fault_ring_at(buf);
+            if async_polling_active() {
+                ring.recv_req_status.store(true, SeqCst);
+            }
+            let sender_id = crate::uintc::register_sender_async_syscall(ntfn_cap);
+            let cid = coroutine_spawn_with_owner(Box::pin(async_fault_ring_handler(buf)), pager.get_ptr());
+            (cid, if sender_id < 0 { None } else { Some(sender_id as usize) })
+        }
+    };
+    debug!("async_fault_ring_register: buf: {:#x}, badge: {}, cid: {:?}", buf, badge, cid);
+    maps.push(FaultRingMap { buf, cid, pager: pager.get_ptr(), tcb: tcb.get_ptr(), badge, ntfn: ntfn_cap.get_nf_ptr(), sender_id });
+    Ok(())
+}
+
+// 注销一个注册，阻塞在该ring上的fault线程不会再被恢复
+fn release_fault_ring(index: usize) {
+    let map = unsafe { FAULT_RING_MAP.remove(index) };
+    debug!("release_fault_ring: buf: {:#x}, badge: {}", map.buf, map.badge);
+    let thread = convert_to_mut_type_ref::<tcb_t>(map.tcb);
+    if thread.get_state() == ThreadState::ThreadStateBlockedOnReply && thread.tcbFault.get_fault_type() != FaultType::NullFault {
+        thread.tcbFault = seL4_Fault_t::new_null_fault();
+        set_thread_state(thread, ThreadState::ThreadStateInactive);
+    }
+    let remaining = unsafe { FAULT_RING_MAP.iter().find(|other| other.buf == map.buf).map(|other| other.pager) };
+    if let Some(pager) = remaining {
+        // 处理协程可能属于被注销的pager，pager被删除时其协程也被取消，转给剩余注册项的pager
+        if unsafe { !FAULT_RING_MAP.iter().any(|other| other.buf == map.buf && other.pager == map.pager) } {
+            coroutine_cancel(&map.cid);
+            let cid = coroutine_spawn_with_owner(Box::pin(async_fault_ring_handler(map.buf)), pager);
+            for other in unsafe { FAULT_RING_MAP.iter_mut().filter(|other| other.buf == map.buf) } {
+                other.cid = cid;
+            }
+        }
+        return;
+    }
+    coroutine_cancel(&map.cid);
+    if let Some(sender_id) = map.sender_id {
+        crate::uintc::unregister_sender_async_syscall(sender_id);
+    }
+}
+
+pub fn async_fault_ring_release_by<F: Fn(&FaultRingMap) -> bool>(f: F) {
+    unsafe {
+        while let Some(index) = FAULT_RING_MAP.iter().position(|map| f(map)) {
+            release_fault_ring(index);
+        }
+    }
+}
+
+// 唤醒pager注册的fault ring的处理协程
+pub fn async_fault_ring_wake(pager: &tcb_t) {
+    for map in unsafe { FAULT_RING_MAP.iter() } {
+        if map.pager == pager.get_ptr() {
+            coroutine_wake(&map.cid);
+        }
+    }
+}
+
+// 由handle_fault调用，线程注册了fault ring时将fault写入ring并阻塞线程
+// 返回false时由调用者发送fault IPC（线程没有注册或ring已满）
+pub fn async_fault_deliver(thread: &mut tcb_t) -> bool {
+    let map = match unsafe { FAULT_RING_MAP.iter().find(|map| map.tcb == thread.get_ptr()) } {
+        Some(map) => map,
+        None => return false,
+    };
+    let ring = fault_ring_at(map.buf);
+    let fault = unsafe { current_fault };
+    let record = fault_record(thread, &fault, map.badge);
+    if ring.res_items.write_free_item(&record).is_err() {
+        debug!("async_fault_deliver: fault ring {:#x} is full", map.buf);
+        return false;
+    }
+    thread.tcbFault = fault;
+    if fault.get_fault_type() == FaultType::CapFault {
+        thread.tcbLookupFailure = unsafe { current_lookup_fault };
+    }
+    set_thread_state(thread, ThreadState::ThreadStateBlockedOnReply);
+    if ring.recv_reply_status.load(SeqCst) == false {
+        ring.recv_reply_status.store(true, SeqCst);
+        if let Some(sender_id) = map.sender_id {
+            unsafe { send_async_syscall_uintr(sender_id); }
+        }
+    }
+    true
+}
+
+// 按fault IPC的消息格式生成fault记录
+fn fault_record(thread: &tcb_t, fault: &seL4_Fault_t, badge: usize) -> FaultRecord {
+    let mut record = FaultRecord { badge: badge as u64, label: fault.get_fault_type() as u64, ..Default::default() };
+    let words = &mut record.words;
+    let length = match fault.get_fault_type() {
+        FaultType::CapFault => {
+            words[seL4_CapFault_IP] = thread.get_register(FaultIP) as u64;
+            words[seL4_CapFault_Addr] = fault.cap_fault_get_address() as u64;
+            words[seL4_CapFault_InRecvPhase] = fault.cap_fault_get_in_receive_phase() as u64;
+            let lookup_fault = unsafe { current_lookup_fault };
+            words[seL4_CapFault_LookupFailureType] = (lookup_fault.get_type() + 1) as u64;
+            match lookup_fault.get_lookup_fault_type() {
+                LookupFaultType::InvaildRoot => seL4_CapFault_BitsLeft,
+                LookupFaultType::MissingCap => {
+                    words[seL4_CapFault_BitsLeft] = lookup_fault.missing_cap_get_bits_left() as u64;
+                    seL4_CapFault_DepthMismatch_BitsFound
+                }
+                LookupFaultType::DepthMismatch => {
+                    words[seL4_CapFault_BitsLeft] = lookup_fault.depth_mismatch_get_bits_left() as u64;
+                    words[seL4_CapFault_DepthMismatch_BitsFound] = lookup_fault.depth_mismatch_get_bits_found() as u64;
+                    seL4_CapFault_GuardMismatch_BitsFound
+                }
+                LookupFaultType::GuardMismatch => {
+                    words[seL4_CapFault_BitsLeft] = lookup_fault.guard_mismatch_get_bits_left() as u64;
+                    words[seL4_CapFault_GuardMismatch_GuardFound] = lookup_fault.guard_mismatch_get_guard_found() as u64;
+                    words[seL4_CapFault_GuardMismatch_BitsFound] = lookup_fault.guard_mismatch_get_bits_found() as u64;
+                    seL4_CapFault_GuardMismatch_BitsFound + 1
+                }
+            }
+        }
+        FaultType::UnknownSyscall => {
+            for i in 0..n_syscallMessage {
+                words[i] = thread.get_register(fault_messages[MessageID_Syscall][i]) as u64;
+            }
+            words[n_syscallMessage] = fault.unknown_syscall_get_syscall_number() as u64;
+            n_syscallMessage + 1
+        }
+        FaultType::UserException => {
+            for i in 0..n_exceptionMessage {
+                words[i] = thread.get_register(fault_messages[MessageID_Exception][i]) as u64;
+            }
+            words[n_exceptionMessage] = fault.user_exeception_get_number() as u64;
+            words[n_exceptionMessage + 1] = fault.user_exeception_get_code() as u64;
+            n_exceptionMessage + 2
+        }
+        FaultType::VMFault => {
+            words[seL4_VMFault_IP] = thread.get_register(FaultIP) as u64;
+            words[seL4_VMFault_Addr] = fault.vm_fault_get_address() as u64;
+            words[seL4_VMFault_PrefetchFault] = fault.vm_fault_get_instruction_fault() as u64;
+            words[seL4_VMFault_FSR] = fault.vm_fault_get_fsr() as u64;
+            seL4_VMFault_Length
+        }
+        _ => 0,
+    };
+    record.length = length as u64;
+    record
+}
+
+// 处理一条恢复记录，与fault IPC的回复（do_fault_reply_transfer）一致
+fn fault_ring_resume(buf: usize, record: &FaultRecord) {
+    let map = unsafe { FAULT_RING_MAP.iter().find(|map| map.buf == buf && map.badge == record.badge as usize) };
+    let thread = match map {
+        Some(map) => convert_to_mut_type_ref::<tcb_t>(map.tcb),
+        None => {
+            debug!("fault_ring_resume: no thread with badge {}", record.badge);
+            return;
+        }
+    };
+    if thread.get_state() != ThreadState::ThreadStateBlockedOnReply || thread.tcbFault.get_fault_type() == FaultType::NullFault {
+        debug!("fault_ring_resume: thread with badge {} is not waiting for a resume", record.badge);
+        return;
+    }
+    let length = record.length as usize;
+    let restart = match thread.tcbFault.get_fault_type() {
+        FaultType::UnknownSyscall => {
+            for i in 0..core::cmp::min(length, n_syscallMessage) {
+                thread.set_register(fault_messages[MessageID_Syscall][i], record.words[i] as usize);
+            }
+            record.label == 0
+        }
+        FaultType::UserException => {
+            for i in 0..core::cmp::min(length, n_exceptionMessage) {
+                thread.set_register(fault_messages[MessageID_Exception][i], record.words[i] as usize);
+            }
+            record.label == 0
+        }
+        _ => true,
+    };
+    thread.tcbFault = seL4_Fault_t::new_null_fault();
+    if restart {
+        set_thread_state(thread, ThreadState::ThreadStateRestart);
+        possible_switch_to(thread);
+    } else {
+        set_thread_state(thread, ThreadState::ThreadStateInactive);
+    }
+}
+
+async fn async_fault_ring_handler(buf: usize) {
+    loop {
+        let ring = fault_ring_at(buf);
+        while let Some(record) = ring.req_items.get_first_item() {
+            fault_ring_resume(buf, &record);
+        }
+        if !async_polling_active() {
+            ring.recv_req_status.store(false, SeqCst);
+        }
+        yield_now().await;
+    }
+}
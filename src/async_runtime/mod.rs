@@ -3,77 +3,288 @@ use alloc::vec::Vec;
 use core::future::Future;
 use core::pin::Pin;
 use log::debug;
-pub use crate::async_runtime::coroutine::CoroutineId;
+pub use async_runtime_core::CoroutineId;
 pub use new_buffer::IPCItem;
-use crate::async_runtime::executor::Executor;
+use async_runtime_core::Executor;
+use crate::async_runtime::executor::EXECUTOR_HOOKS;
+use crate::common::{sel4_config::{CONFIG_MAX_NUM_NODES, tcbVTable}, utils::cpu_id};
+use crate::task_manager::tcb_t;
+use crate::syscall::is_valid_vtable_root;
 
-mod coroutine;
 mod new_buffer;
 mod executor;
 mod async_syscall_handler;
+mod async_endpoint;
+mod polling;
+mod timer;
+mod fault_ring;
+mod stats;
 mod utils;
 
 pub use async_syscall_handler::async_syscall_handler;
-pub use new_buffer::{NewBufferMap, NewBuffer};
+pub use async_endpoint::{async_endpoint_wake, async_endpoint_reply, async_endpoint_abort_reply, async_endpoint_drop_caller};
+use async_endpoint::{async_endpoint_cancel, async_endpoint_cancel_ring, async_endpoint_release_server};
+pub use new_buffer::{NewBufferMap, NewBuffer, AsyncRing, AsyncItemVersion, AsyncNotifyPolicy, AsyncNotifyState};
+pub use async_syscall_handler::async_syscall_notify_tick;
+pub use polling::{async_polling_active, async_polling_on, async_polling_valid_hart, async_polling_set, async_polling_poll, async_polling_idle};
+pub use timer::{async_timer_tick, coroutine_now, sleep_until, Sleep};
+pub use fault_ring::{FAULT_RING_MAP, FAULT_RING_SIZE, FaultRecord, async_fault_ring_register, async_fault_ring_wake, async_fault_deliver};
+use fault_ring::async_fault_ring_release_by;
+pub use stats::{AsyncWakeSource, AsyncRingStats, ExecutorStats, async_ring_stats, async_executor_stats};
 
-pub static mut NEW_BUFFER_MAP: Vec<NewBufferMap> = Vec::new();
+// 每个注册项单独分配，处理协程和endpoint协程通过注册项的地址访问，不必按ring查找
+pub static mut NEW_BUFFER_MAP: Vec<Box<NewBufferMap>> = Vec::new();
 
-static mut EXECUTOR: Executor = Executor::new();
+// 注销一个异步系统调用注册：取消该ring的处理协程和endpoint请求，移除NEW_BUFFER_MAP表项并释放UIST表项
+// 同一线程的其他ring不受影响
+fn release_async_syscall(index: usize) {
+    let map = unsafe { NEW_BUFFER_MAP.remove(index) };
+    debug!("release_async_syscall: cid: {:?}, requests: {}, batches: {}, responses: {}, uintr sent: {}", map.cid, map.stats.requests(),
+        map.stats.batches, map.notify.responses, map.notify.uintr_sent);
+    coroutine_cancel(&map.cid);
+    async_endpoint_cancel_ring(&map.buf);
+    if let Some(sender_id) = map.sender_id {
+        crate::uintc::unregister_sender_async_syscall(sender_id);
+    }
+}
+
+fn release_async_syscall_by<F: Fn(&NewBufferMap) -> bool>(f: F) {
+    unsafe {
+        while let Some(index) = NEW_BUFFER_MAP.iter().position(|map| f(map)) {
+            release_async_syscall(index);
+        }
+    }
+}
+
+// 线程被删除时调用
+pub fn async_syscall_release_tcb(tcb: &mut tcb_t) {
+    let tcb_ptr = tcb.get_ptr();
+    release_async_syscall_by(|map| map.tcb == tcb_ptr);
+    // 释放该线程在其他共享ring中的提交者badge
+    for map in unsafe { NEW_BUFFER_MAP.iter_mut() } {
+        for submitter in map.submitters.iter_mut().filter(|submitter| **submitter == tcb_ptr) {
+            *submitter = 0;
+        }
+    }
+    async_fault_ring_release_by(|map| map.tcb == tcb_ptr || map.pager == tcb_ptr);
+    // 没有注册项的协程同样不能再访问该线程
+    let cancelled = coroutine_cancel_owned_by(tcb_ptr);
+    async_endpoint_cancel(&cancelled);
+    async_endpoint_release_server(tcb);
+}
+
+// notification不再是用户态中断的接收者时调用（线程解绑或注销接收者），ring和处理协程保留，
+// 只释放指向该notification的内核发送者表项，之后的响应不再发送用户态中断
+pub fn async_syscall_detach_ntfn(ntfn_ptr: usize) {
+    let mut released = Vec::new();
+    unsafe {
+        let sender_ids = NEW_BUFFER_MAP.iter_mut().filter(|map| map.ntfn == ntfn_ptr).map(|map| &mut map.sender_id)
+            .chain(FAULT_RING_MAP.iter_mut().filter(|map| map.ntfn == ntfn_ptr).map(|map| &mut map.sender_id));
+        for sender_id in sender_ids {
+            // 共享fault ring的多个注册项使用同一个表项
+            if let Some(sender_id) = sender_id.take().filter(|sender_id| !released.contains(sender_id)) {
+                crate::uintc::unregister_sender_async_syscall(sender_id);
+                released.push(sender_id);
+            }
+        }
+    }
+}
+
+// notification被删除时调用
+pub fn async_syscall_release_ntfn(ntfn_ptr: usize) {
+    release_async_syscall_by(|map| map.ntfn == ntfn_ptr);
+    async_fault_ring_release_by(|map| map.ntfn == ntfn_ptr);
+}
+
+// NewBuffer所在的frame被删除时调用
+pub fn async_syscall_release_buffer(frame_ptr: usize) {
+    release_async_syscall_by(|map| map.buf.get_ptr() == frame_ptr);
+    async_fault_ring_release_by(|map| map.buf == frame_ptr);
+}
 
 #[inline]
-pub fn coroutine_spawn(future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>) -> CoroutineId {
+pub fn async_syscall_is_registered(cid: &CoroutineId) -> bool {
+    unsafe { NEW_BUFFER_MAP.iter().any(|map| map.cid == *cid) }
+}
+
+// 线程的地址空间（根页表的地址），没有有效的根页表时为None
+pub fn async_syscall_vspace_of(tcb: &tcb_t) -> Option<usize> {
+    let cap = tcb.get_cspace(tcbVTable).cap;
+    if is_valid_vtable_root(&cap) {
+        Some(cap.get_pt_base_ptr())
+    } else {
+        None
+    }
+}
+
+// 线程是否可以向ring提交请求：注册该ring的线程，或已加入共享ring的提交线程
+pub fn async_syscall_can_submit(map: &NewBufferMap, tcb: &tcb_t) -> bool {
+    map.tcb == tcb.get_ptr() || map.submitters.contains(&tcb.get_ptr())
+}
+
+// 提交请求时msg_info高16位可用的最大badge
+const ASYNC_SUBMITTER_MAX: usize = 0xffff;
+
+// 线程加入通过ntfn注册、位于buf_ptr的共享ring，返回该线程提交请求时使用的badge（注册线程自己为0），
+// 只有与ring在同一地址空间中的线程可以加入，ring不存在、不是共享ring或badge用尽时返回None
+pub fn async_syscall_add_submitter(ntfn_ptr: usize, buf_ptr: usize, tcb: &tcb_t) -> Option<usize> {
+    let map = unsafe { NEW_BUFFER_MAP.iter_mut().find(|map| map.ntfn == ntfn_ptr && map.buf.get_ptr() == buf_ptr)? };
+    if map.vspace.is_none() || map.vspace != async_syscall_vspace_of(tcb) {
+        return None;
+    }
+    if map.tcb == tcb.get_ptr() {
+        return Some(0);
+    }
+    if let Some(index) = map.submitters.iter().position(|tcb_ptr| *tcb_ptr == tcb.get_ptr()) {
+        return Some(index + 1);
+    }
+    let index = match map.submitters.iter().position(|tcb_ptr| *tcb_ptr == 0) {
+        Some(index) => index,
+        None if map.submitters.len() < ASYNC_SUBMITTER_MAX => {
+            map.submitters.push(0);
+            map.submitters.len() - 1
+        }
+        None => return None,
+    };
+    map.submitters[index] = tcb.get_ptr();
+    Some(index + 1)
+}
+
+// 每个核一个执行器，与ksSMP一样按核号索引
+const EXECUTOR_INIT: Executor = Executor::new(EXECUTOR_HOOKS);
+static mut EXECUTORS: [Executor; CONFIG_MAX_NUM_NODES] = [EXECUTOR_INIT; CONFIG_MAX_NUM_NODES];
+
+#[inline]
+fn current_executor() -> &'static Executor {
     unsafe {
-        EXECUTOR.spawn(future)
+        &EXECUTORS[cpu_id()]
     }
 }
 
 #[inline]
-pub fn coroutine_wake(cid: &CoroutineId) {
+pub fn coroutine_spawn(future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>) -> CoroutineId {
+    current_executor().spawn(future, 0)
+}
+
+// 为线程生成协程，协程按该线程的优先级和调度域被调度
+#[inline]
+pub fn coroutine_spawn_with_owner(future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>, owner: usize) -> CoroutineId {
+    current_executor().spawn(future, owner)
+}
+
+fn wake_in_owner(cid: &CoroutineId, value: Option<u64>) {
+    // 先查本核，再查其他核的执行器
+    if current_executor().wake(cid, value) {
+        return;
+    }
     unsafe {
-        EXECUTOR.wake(cid);
+        for (index, executor) in EXECUTORS.iter().enumerate() {
+            if index != cpu_id() && executor.wake(cid, value) {
+                return;
+            }
+        }
     }
 }
 
+#[inline]
+pub fn coroutine_wake(cid: &CoroutineId) {
+    wake_in_owner(cid, None);
+}
+
 #[inline]
 pub fn coroutine_wake_with_value(cid: &CoroutineId, value: u64) {
+    wake_in_owner(cid, Some(value));
+}
+
+// 将挂起的协程唤醒到指定核的执行器上，协程不处于挂起状态时退化为普通唤醒
+pub fn coroutine_wake_on(cid: &CoroutineId, cpu: usize) {
     unsafe {
-        EXECUTOR.immediate_value.insert(*cid, value);
-        EXECUTOR.wake(cid);
+        for executor in EXECUTORS.iter() {
+            if let Some(coroutine) = executor.take_pending(cid) {
+                EXECUTORS[cpu].push_ready(coroutine);
+                return;
+            }
+        }
     }
+    coroutine_wake(cid);
 }
 
-#[inline]
-pub fn coroutine_get_immediate_value(cid: &CoroutineId) -> Option<u64> {
+// 在所有核的执行器中取消属于某个线程的协程
+pub fn coroutine_cancel_owned_by(owner: usize) -> Vec<CoroutineId> {
+    let mut cancelled = Vec::new();
     unsafe {
-        EXECUTOR.immediate_value.remove(cid)
+        for executor in EXECUTORS.iter() {
+            cancelled.append(&mut executor.cancel_owned_by(owner));
+        }
     }
+    cancelled
+}
+
+// 取消一个协程，协程不存在（已结束）时返回false
+pub fn coroutine_cancel(cid: &CoroutineId) -> bool {
+    unsafe { EXECUTORS.iter().any(|executor| executor.cancel(cid)) }
+}
+
+#[inline]
+pub fn coroutine_get_immediate_value(cid: &CoroutineId) -> Option<u64> {
+    current_executor().take_immediate_value(cid)
 }
 
 #[inline]
 pub fn coroutine_get_current() -> CoroutineId {
-    unsafe {
-        EXECUTOR.current.unwrap()
-    }
+    current_executor().current().unwrap()
+}
+
+// 当前执行器本次开始执行协程的原因
+#[inline]
+pub fn coroutine_run_source() -> AsyncWakeSource {
+    current_executor().source()
+}
+
+// 读取某个核的执行器的统计，调用者已检查cpu合法
+#[inline]
+pub fn executor_stats(cpu: usize, reset: bool) -> ExecutorStats {
+    unsafe { EXECUTORS[cpu].stats(reset) }
 }
 
 #[inline]
 pub fn get_executor_ptr() -> usize {
+    current_executor() as *const Executor as usize
+}
+
+// 从其他核的就绪队列中窃取协程，返回是否窃取到
+fn coroutine_steal() -> bool {
     unsafe {
-        &EXECUTOR as *const Executor as usize
+        for (index, executor) in EXECUTORS.iter().enumerate() {
+            if index == cpu_id() {
+                continue;
+            }
+            let stolen = executor.steal();
+            if !stolen.is_empty() {
+                for coroutine in stolen {
+                    current_executor().push_ready(coroutine);
+                }
+                return true;
+            }
+        }
     }
+    false
 }
 
+// 执行本核的就绪协程，source为开始执行的原因（用于统计）
 #[inline]
-pub fn coroutine_run_until_blocked() {
-    unsafe {
-        EXECUTOR.run_until_blocked()
+pub fn coroutine_run_until_blocked(source: AsyncWakeSource) {
+    current_executor().set_source(source);
+    loop {
+        current_executor().run_until_blocked();
+        if !coroutine_steal() {
+            break;
+        }
     }
 }
 
 
 #[inline]
 pub fn coroutine_run_until_complete() {
-    unsafe {
-        EXECUTOR.run_until_complete()
-    }
-}
\ No newline at end of file
+    current_executor().run_until_complete()
+}
@@ -1,17 +1,50 @@
-use crate::async_runtime::coroutine::CoroutineId;
-use crate::async_runtime::utils::{RingBuffer, SafeRingBuffer};
+use alloc::vec::Vec;
+use async_runtime_core::{CoroutineId, SafeRingBuffer};
+use crate::async_runtime::stats::AsyncRingStats;
+use crate::common::{message_info::AsyncMessageLabel, sel4_config::seL4_IPCBufferSizeBits};
 use core::sync::atomic::AtomicBool;
 use spin::Mutex;
 // use sel4::r#yield;
 
-pub const MAX_ITEM_NUM: usize = 4096;
+pub use async_runtime_core::MAX_ITEM_NUM;
 pub const MAX_IPC_MSG_LEN: usize = 16;
-#[repr(align(8))]
+
+// item布局版本，在UintrRegisterAsyncSyscall时协商
+// V1: extend_msg为16个u16，地址等参数需要移位或拆分到多个槽中
+// V2: extend_msg为16个u64
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AsyncItemVersion {
+    V1 = 1,
+    V2 = 2,
+}
+
+pub const ASYNC_ITEM_VERSION_MAX: AsyncItemVersion = AsyncItemVersion::V2;
+
+impl AsyncItemVersion {
+    // 用户态请求的版本，0表示未指定（旧的用户态程序），超过内核支持的最高版本时使用最高版本
+    pub fn negotiate(requested: usize) -> Self {
+        match requested {
+            0 | 1 => AsyncItemVersion::V1,
+            _ => ASYNC_ITEM_VERSION_MAX,
+        }
+    }
+
+    // 该版本的ring（NewBuffer）占用的字节数，承载ring的frame不能小于该值
+    pub fn ring_size(&self) -> usize {
+        match self {
+            AsyncItemVersion::V1 => core::mem::size_of::<NewBuffer<LegacyIPCItem>>(),
+            AsyncItemVersion::V2 => core::mem::size_of::<NewBuffer<IPCItem>>(),
+        }
+    }
+}
+
+// 内核内部使用的item，与V2布局相同
+#[repr(C, align(8))]
 #[derive(Clone, Copy, Debug)]
 pub struct IPCItem {
     pub cid: CoroutineId,
     pub msg_info: u32,
-    pub extend_msg: [u16; MAX_IPC_MSG_LEN],
+    pub extend_msg: [u64; MAX_IPC_MSG_LEN],
 }
 
 impl Default for IPCItem {
@@ -29,7 +62,7 @@ impl IPCItem {
         Self {
             cid: CoroutineId(0),
             msg_info: 0,
-            extend_msg: [0u16; MAX_IPC_MSG_LEN],
+            extend_msg: [0u64; MAX_IPC_MSG_LEN],
         }
     }
 
@@ -37,17 +70,89 @@ impl IPCItem {
         Self {
             cid,
             msg_info: msg,
-            extend_msg: [0u16; MAX_IPC_MSG_LEN],
+            extend_msg: [0u64; MAX_IPC_MSG_LEN],
+        }
+    }
+}
+
+// V1布局的item
+#[repr(C, align(8))]
+#[derive(Clone, Copy, Debug)]
+pub struct LegacyIPCItem {
+    pub cid: CoroutineId,
+    pub msg_info: u32,
+    pub extend_msg: [u16; MAX_IPC_MSG_LEN],
+}
+
+impl Default for LegacyIPCItem {
+    fn default() -> Self {
+        Self {
+            cid: Default::default(),
+            msg_info: 0,
+            extend_msg: [0; MAX_IPC_MSG_LEN],
         }
     }
 }
 
-pub struct ItemsQueue {
-    buffer: SafeRingBuffer<IPCItem, MAX_ITEM_NUM>,
+impl LegacyIPCItem {
+    // 请求：还原被移位和拆分的参数
+    pub fn to_item(&self) -> IPCItem {
+        let mut item = IPCItem::from(self.cid, self.msg_info);
+        for i in 0..MAX_IPC_MSG_LEN {
+            item.extend_msg[i] = self.extend_msg[i] as u64;
+        }
+        match AsyncMessageLabel::from(self.msg_info) {
+            AsyncMessageLabel::RISCVPageTableMap | AsyncMessageLabel::RISCVPageMap => {
+                item.extend_msg[2] <<= 12;
+            }
+            AsyncMessageLabel::TCBConfigure => {
+                item.extend_msg[6] <<= seL4_IPCBufferSizeBits;
+            }
+            AsyncMessageLabel::TCBSetIPCBuffer => {
+                item.extend_msg[1] <<= seL4_IPCBufferSizeBits;
+            }
+            AsyncMessageLabel::TCBSetTLSBase | AsyncMessageLabel::Timeout => {
+                item.extend_msg[1] = Self::join(&self.extend_msg[1..5]);
+            }
+            _ => {}
+        }
+        item
+    }
+
+    // 响应：拆分超过16位的返回值
+    pub fn from_item(item: &IPCItem) -> Self {
+        let mut legacy = Self { cid: item.cid, msg_info: item.msg_info, extend_msg: [0; MAX_IPC_MSG_LEN] };
+        for i in 0..MAX_IPC_MSG_LEN {
+            legacy.extend_msg[i] = item.extend_msg[i] as u16;
+        }
+        match AsyncMessageLabel::from(item.msg_info) {
+            AsyncMessageLabel::RISCVPageGetAddress => {
+                Self::split(item.extend_msg[1], &mut legacy.extend_msg[1..5]);
+            }
+            _ => {}
+        }
+        legacy
+    }
+
+    fn join(slots: &[u16]) -> u64 {
+        slots.iter().fold(0, |acc, slot| (acc << 16) | (*slot as u64))
+    }
+
+    fn split(value: u64, slots: &mut [u16]) {
+        let n = slots.len();
+        for (i, slot) in slots.iter_mut().enumerate() {
+            *slot = (value >> (16 * (n - 1 - i))) as u16;
+        }
+    }
+}
+
+#[repr(C)]
+pub struct ItemsQueue<T = IPCItem> {
+    buffer: SafeRingBuffer<T, MAX_ITEM_NUM>,
     // lock: Mutex<()>,
 }
 
-impl ItemsQueue {
+impl<T> ItemsQueue<T> where T: Default + Copy + Clone {
     pub fn new() -> Self {
         Self {
             buffer: SafeRingBuffer::new(),
@@ -55,26 +160,38 @@ impl ItemsQueue {
     }
 
     #[inline]
-    pub fn write_free_item(&mut self, item: &IPCItem) -> Result<(), ()> {
+    pub fn write_free_item(&self, item: &T) -> Result<(), ()> {
         return self.buffer.push_safe(item);
     }
 
     #[inline]
-    pub fn get_first_item(&mut self) -> Option<IPCItem> {
+    pub fn get_first_item(&self) -> Option<T> {
         return self.buffer.pop_safe();
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buffer.size()
+    }
 }
 
 
-#[repr(align(4096))]
-pub struct NewBuffer {
+// 用户态与内核共享的NewBuffer，布局（repr(C)）：
+// - 偏移 0: recv_req_status，内核协程是否在处理请求，为false时用户态需要唤醒内核协程
+// - 偏移 1: recv_reply_status，用户态是否在处理响应，为false时内核需要发送用户态中断
+// - 偏移 64: req_items，用户态生产、内核消费的请求队列，布局见SafeRingBuffer，内核不向其中写入
+// - 其后: res_items，内核生产、用户态消费的响应队列，包括请求的响应和cid为0的外设中断通知，
+//   内核中的生产者（处理协程、endpoint协程、中断处理）都持有内核锁，对该队列仍是单生产者
+// 队列元素为协商版本对应的item（V1为LegacyIPCItem，V2为IPCItem），两者都是repr(C)
+#[repr(C, align(4096))]
+pub struct NewBuffer<T = IPCItem> {
     pub recv_req_status: AtomicBool,
     pub recv_reply_status: AtomicBool,
-    pub req_items: ItemsQueue,
-    pub res_items: ItemsQueue,
+    pub req_items: ItemsQueue<T>,
+    pub res_items: ItemsQueue<T>,
 }
 
-impl NewBuffer {
+impl<T> NewBuffer<T> where T: Default + Copy + Clone {
     pub fn new() -> Self {
         Self {
             recv_req_status: AtomicBool::new(false),
@@ -96,7 +213,155 @@ impl NewBuffer {
     }
 }
 
+// 按协商的版本访问用户态的NewBuffer
+#[derive(Clone, Copy, Debug)]
+pub struct AsyncRing {
+    ptr: usize,
+    pub version: AsyncItemVersion,
+}
+
+impl AsyncRing {
+    pub const fn new(ptr: usize, version: AsyncItemVersion) -> Self {
+        Self { ptr, version }
+    }
+
+    #[inline]
+    pub fn get_ptr(&self) -> usize {
+        self.ptr
+    }
+
+    #[inline]
+    pub fn recv_req_status(&self) -> &'static AtomicBool {
+        match self.version {
+            AsyncItemVersion::V1 => &NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).recv_req_status,
+            AsyncItemVersion::V2 => &NewBuffer::<IPCItem>::from_ptr(self.ptr).recv_req_status,
+        }
+    }
+
+    #[inline]
+    pub fn recv_reply_status(&self) -> &'static AtomicBool {
+        match self.version {
+            AsyncItemVersion::V1 => &NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).recv_reply_status,
+            AsyncItemVersion::V2 => &NewBuffer::<IPCItem>::from_ptr(self.ptr).recv_reply_status,
+        }
+    }
+
+    pub fn pop_request(&self) -> Option<IPCItem> {
+        match self.version {
+            AsyncItemVersion::V1 => NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).req_items.get_first_item().map(|item| item.to_item()),
+            AsyncItemVersion::V2 => NewBuffer::<IPCItem>::from_ptr(self.ptr).req_items.get_first_item(),
+        }
+    }
+
+    // 请求队列中未处理的请求数
+    pub fn request_len(&self) -> usize {
+        match self.version {
+            AsyncItemVersion::V1 => NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).req_items.len(),
+            AsyncItemVersion::V2 => NewBuffer::<IPCItem>::from_ptr(self.ptr).req_items.len(),
+        }
+    }
+
+    // 响应队列中用户态未取走的响应数
+    pub fn response_len(&self) -> usize {
+        match self.version {
+            AsyncItemVersion::V1 => NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).res_items.len(),
+            AsyncItemVersion::V2 => NewBuffer::<IPCItem>::from_ptr(self.ptr).res_items.len(),
+        }
+    }
+
+    // 请求队列中是否有未处理的请求
+    pub fn has_request(&self) -> bool {
+        match self.version {
+            AsyncItemVersion::V1 => NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).req_items.len() != 0,
+            AsyncItemVersion::V2 => NewBuffer::<IPCItem>::from_ptr(self.ptr).req_items.len() != 0,
+        }
+    }
+
+    pub fn push_response(&self, item: &IPCItem) -> Result<(), ()> {
+        match self.version {
+            AsyncItemVersion::V1 => NewBuffer::<LegacyIPCItem>::from_ptr(self.ptr).res_items.write_free_item(&LegacyIPCItem::from_item(item)),
+            AsyncItemVersion::V2 => NewBuffer::<IPCItem>::from_ptr(self.ptr).res_items.write_free_item(item),
+        }
+    }
+}
+
+// 完成通知（发送给用户态的中断）的合并策略，在UintrRegisterAsyncSyscall时设置
+// 只有用户态没有在处理响应（recv_reply_status为false）时写入的响应才需要通知
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AsyncNotifyPolicy {
+    // 每个响应都立即通知
+    Immediate,
+    // 累积N个未通知的响应后通知，请求队列处理完时也会通知剩余的响应
+    Batch(usize),
+    // 请求队列处理完时通知
+    Drain,
+    // 第一个未通知的响应写入后经过指定的时间（微秒）通知，精度为内核时钟中断的间隔
+    Timer(usize),
+}
+
+// Timer策略允许的最长延迟（微秒）
+pub const ASYNC_NOTIFY_TIMER_MAX_US: usize = 1_000_000;
+
+impl AsyncNotifyPolicy {
+    // 注册时的参数：mode为0~3分别对应Immediate、Batch、Drain、Timer，param为N或微秒数（不超过ASYNC_NOTIFY_TIMER_MAX_US）
+    pub fn from_args(mode: usize, param: usize) -> Result<Self, usize> {
+        match mode {
+            0 => Ok(AsyncNotifyPolicy::Immediate),
+            1 if param > 0 => Ok(AsyncNotifyPolicy::Batch(param)),
+            2 => Ok(AsyncNotifyPolicy::Drain),
+            3 if param > 0 && param <= ASYNC_NOTIFY_TIMER_MAX_US => Ok(AsyncNotifyPolicy::Timer(param)),
+            1 | 3 => Err(2),
+            _ => Err(1),
+        }
+    }
+}
+
+// 每个buffer的完成通知状态和计数
+pub struct AsyncNotifyState {
+    pub policy: AsyncNotifyPolicy,
+    // 尚未通知的响应数
+    pub pending: usize,
+    // Timer策略下的通知时间（time寄存器的值）
+    pub deadline: usize,
+    // 处理协程是否已处理完请求队列
+    pub idle: bool,
+    // 写入的响应总数
+    pub responses: usize,
+    // 发送的用户态中断数
+    pub uintr_sent: usize,
+}
+
+impl AsyncNotifyState {
+    pub const fn new(policy: AsyncNotifyPolicy) -> Self {
+        Self { policy, pending: 0, deadline: 0, idle: true, responses: 0, uintr_sent: 0 }
+    }
+}
+
 pub struct NewBufferMap {
-    pub buf: &'static mut NewBuffer,
+    pub buf: AsyncRing,
     pub cid: CoroutineId,
-}
\ No newline at end of file
+    // 注册该buffer的线程
+    pub tcb: usize,
+    // 用于通知用户态的notification
+    pub ntfn: usize,
+    // 内核发送者UIST中的表项，注册失败时为None
+    pub sender_id: Option<usize>,
+    pub notify: AsyncNotifyState,
+    // 地址空间共享的ring为注册线程的根页表地址，同一地址空间中的线程通过UintrAddAsyncSubmitter加入后可以提交请求
+    pub vspace: Option<usize>,
+    // 加入共享ring的提交线程，请求中的提交者badge为下标+1，0表示该badge已被释放
+    pub submitters: Vec<usize>,
+    pub stats: AsyncRingStats,
+}
+
+impl NewBufferMap {
+    #[inline]
+    pub fn get_ptr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    #[inline]
+    pub fn from_ptr(ptr: usize) -> &'static mut Self {
+        unsafe { &mut *(ptr as *mut Self) }
+    }
+}
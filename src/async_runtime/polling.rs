@@ -0,0 +1,124 @@
+use core::sync::atomic::Ordering::SeqCst;
+use log::debug;
+use riscv::register::sstatus;
+use crate::async_runtime::{NEW_BUFFER_MAP, FAULT_RING_MAP, FaultRecord, NewBuffer, coroutine_wake, coroutine_wake_on, coroutine_run_until_blocked, AsyncWakeSource};
+use crate::common::{sel4_config::CONFIG_MAX_NUM_NODES, utils::cpu_id};
+use crate::config::CONFIG_ASYNC_POLLING_HARTS;
+use crate::task_manager::{get_ks_scheduler_action, SchedulerAction_ResumeCurrentThread};
+#[cfg(feature = "ENABLE_SMP")]
+use crate::deps::{clh_lock_acquire, clh_lock_release, ipi_send_mask};
+#[cfg(feature = "ENABLE_SMP")]
+use crate::config::IRQConst::INTERRUPT_IPI_2;
+
+// 异步系统调用轮询核（类似io_uring的SQPOLL）
+// 轮询核空闲时不执行wfi，而是持续检查所有注册的NewBuffer，有请求时在本核唤醒并执行对应的处理协程。
+// 存在轮询核时所有NewBuffer（包括fault ring）的recv_req_status保持为true，用户态提交请求后不需要陷入内核唤醒处理协程。
+// 0号核不能作为轮询核；轮询核上不能再绑定线程，已有的线程仍然在该核上运行，轮询核只在空闲时轮询。
+
+// 轮询核（按位）
+static mut ASYNC_POLLING_HARTS: usize = CONFIG_ASYNC_POLLING_HARTS & !1;
+
+// 连续没有请求时两轮轮询之间的最大空转次数，避免轮询核持续争抢内核锁
+const ASYNC_POLLING_BACKOFF_MAX: usize = 1 << 12;
+
+// 每个轮询核当前的空转次数
+static mut ASYNC_POLLING_BACKOFF: [usize; CONFIG_MAX_NUM_NODES] = [0; CONFIG_MAX_NUM_NODES];
+
+#[inline]
+pub fn async_polling_active() -> bool {
+    unsafe { ASYNC_POLLING_HARTS != 0 }
+}
+
+#[inline]
+pub fn async_polling_on(cpu: usize) -> bool {
+    unsafe { ASYNC_POLLING_HARTS & (1 << cpu) != 0 }
+}
+
+#[inline]
+pub fn async_polling_valid_hart(cpu: usize) -> bool {
+    cpu != 0 && cpu < CONFIG_MAX_NUM_NODES
+}
+
+// 设置或取消轮询核，调用者已检查cpu合法
+pub fn async_polling_set(cpu: usize, enable: bool) {
+    let was_active = async_polling_active();
+    unsafe {
+        if enable {
+            ASYNC_POLLING_HARTS |= 1 << cpu;
+        } else {
+            ASYNC_POLLING_HARTS &= !(1 << cpu);
+        }
+    }
+    debug!("async_polling_set: polling harts: {:#x}", unsafe { ASYNC_POLLING_HARTS });
+    if !was_active && async_polling_active() {
+        // 用户态之后不再需要唤醒处理协程
+        for map in unsafe { NEW_BUFFER_MAP.iter() } {
+            map.buf.recv_req_status().store(true, SeqCst);
+        }
+        for map in unsafe { FAULT_RING_MAP.iter() } {
+            NewBuffer::<FaultRecord>::from_ptr(map.buf).recv_req_status.store(true, SeqCst);
+        }
+    } else if was_active && !async_polling_active() {
+        // 唤醒所有处理协程，由其处理完剩余请求后将recv_req_status置为false
+        for map in unsafe { NEW_BUFFER_MAP.iter() } {
+            coroutine_wake(&map.cid);
+        }
+        for map in unsafe { FAULT_RING_MAP.iter() } {
+            coroutine_wake(&map.cid);
+        }
+    }
+}
+
+// 轮询核的空闲线程调用，完成一轮轮询；没有请求时按指数退避空转，再开始下一轮
+pub fn async_polling_idle() {
+    let cpu = cpu_id();
+    let backoff = unsafe { &mut ASYNC_POLLING_BACKOFF[cpu] };
+    if async_polling_poll() {
+        *backoff = 0;
+    } else {
+        *backoff = (*backoff * 2).clamp(1, ASYNC_POLLING_BACKOFF_MAX);
+    }
+    for _ in 0..*backoff {
+        core::hint::spin_loop();
+    }
+}
+
+// 关中断并持有内核锁完成一轮轮询，返回是否有ring中有请求
+// 处理协程唤醒了本核上的线程时向本核发送ipi，由中断返回路径完成调度
+pub fn async_polling_poll() -> bool {
+    let cpu = cpu_id();
+    let mut found = false;
+    unsafe {
+        sstatus::clear_sie();
+        #[cfg(feature = "ENABLE_SMP")]
+        clh_lock_acquire(cpu, false);
+    }
+    for map in unsafe { NEW_BUFFER_MAP.iter() } {
+        if map.buf.has_request() {
+            // 只迁移挂起的处理协程，已在就绪队列中的协程由所在核执行
+            coroutine_wake_on(&map.cid, cpu);
+            found = true;
+        }
+    }
+    for map in unsafe { FAULT_RING_MAP.iter() } {
+        if NewBuffer::<FaultRecord>::from_ptr(map.buf).req_items.len() != 0 {
+            coroutine_wake_on(&map.cid, cpu);
+            found = true;
+        }
+    }
+    if found {
+        coroutine_run_until_blocked(AsyncWakeSource::Polling);
+    }
+    let reschedule = get_ks_scheduler_action() != SchedulerAction_ResumeCurrentThread;
+    unsafe {
+        #[cfg(feature = "ENABLE_SMP")]
+        {
+            if reschedule {
+                ipi_send_mask(INTERRUPT_IPI_2 as usize, 1 << cpu, false);
+            }
+            clh_lock_release(cpu);
+        }
+        sstatus::set_sie();
+    }
+    found || reschedule
+}
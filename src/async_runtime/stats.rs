@@ -0,0 +1,78 @@
+use alloc::vec::Vec;
+use crate::async_runtime::{NEW_BUFFER_MAP, executor_stats};
+use crate::common::{message_info::AsyncMessageLabel, sel4_config::CONFIG_MAX_NUM_NODES};
+pub use async_runtime_core::{AsyncWakeSource, ExecutorStats, ASYNC_WAKE_SOURCE_NUM};
+
+// 异步系统调用路径的统计计数，通过notification上的UintrGetAsyncStats调用读取
+// 时间单位为time寄存器的周期数
+
+pub const ASYNC_LABEL_NUM: usize = AsyncMessageLabel::UnknownLabel as usize + 1;
+
+// 每个ring的统计，保存在NEW_BUFFER_MAP的表项中
+// 响应数和发送的用户态中断数见AsyncNotifyState
+#[derive(Clone, Copy)]
+pub struct AsyncRingStats {
+    // 处理的请求数（按label）
+    pub labels: [usize; ASYNC_LABEL_NUM],
+    // 处理协程处理的轮数
+    pub batches: usize,
+    // 处理协程处理请求的总时间
+    pub run_cycles: usize,
+    // 处理协程开始一轮处理时观察到的请求队列最大长度
+    pub req_depth_max: usize,
+    // 写入响应后响应队列的最大长度
+    pub res_depth_max: usize,
+    // Syscall为被SysWakeSyscallHandler唤醒的次数，其他为在该原因下开始处理的轮数
+    pub wakeups: [usize; ASYNC_WAKE_SOURCE_NUM],
+}
+
+impl AsyncRingStats {
+    pub const fn new() -> Self {
+        Self {
+            labels: [0; ASYNC_LABEL_NUM],
+            batches: 0,
+            run_cycles: 0,
+            req_depth_max: 0,
+            res_depth_max: 0,
+            wakeups: [0; ASYNC_WAKE_SOURCE_NUM],
+        }
+    }
+
+    #[inline]
+    pub fn requests(&self) -> usize {
+        self.labels.iter().sum()
+    }
+}
+
+// UintrGetAsyncStats返回的ring统计（按消息寄存器顺序）：
+// 请求数、处理轮数、处理时间、响应数、用户态中断数、当前请求队列长度、最大请求队列长度、
+// 当前响应队列长度、最大响应队列长度、按来源的唤醒次数（ASYNC_WAKE_SOURCE_NUM个）、按label的请求数（ASYNC_LABEL_NUM个）
+// index为通过该notification注册的第index个ring，不存在时返回None
+pub fn async_ring_stats(ntfn: usize, index: usize, reset: bool) -> Option<Vec<usize>> {
+    let map = unsafe { NEW_BUFFER_MAP.iter_mut().filter(|map| map.ntfn == ntfn).nth(index)? };
+    let stats = &map.stats;
+    let mut words = Vec::with_capacity(9 + ASYNC_WAKE_SOURCE_NUM + ASYNC_LABEL_NUM);
+    words.extend_from_slice(&[stats.requests(), stats.batches, stats.run_cycles, map.notify.responses, map.notify.uintr_sent,
+        map.buf.request_len(), stats.req_depth_max, map.buf.response_len(), stats.res_depth_max]);
+    words.extend_from_slice(&stats.wakeups);
+    words.extend_from_slice(&stats.labels);
+    if reset {
+        map.stats = AsyncRingStats::new();
+        map.notify.responses = 0;
+        map.notify.uintr_sent = 0;
+    }
+    Some(words)
+}
+
+// UintrGetAsyncStats返回的执行器统计（按消息寄存器顺序）：
+// 生成的协程数、结束的协程数、被窃取的协程数、poll次数、poll时间、最大就绪队列长度、按原因的执行次数（ASYNC_WAKE_SOURCE_NUM个）
+pub fn async_executor_stats(cpu: usize, reset: bool) -> Option<Vec<usize>> {
+    if cpu >= CONFIG_MAX_NUM_NODES {
+        return None;
+    }
+    let stats = executor_stats(cpu, reset);
+    let mut words = Vec::with_capacity(6 + ASYNC_WAKE_SOURCE_NUM);
+    words.extend_from_slice(&[stats.spawned, stats.completed, stats.stolen, stats.polls, stats.run_cycles, stats.ready_max]);
+    words.extend_from_slice(&stats.runs);
+    Some(words)
+}
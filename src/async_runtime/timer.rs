@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+use crate::common::{sel4_config::CONFIG_MAX_NUM_NODES, utils::cpu_id};
+use crate::config::RESET_CYCLES;
+use crate::riscv::read_time;
+
+// 协程的时间基准为time寄存器的值（与用户态rdtime读到的值一致）
+// 每个核一个时间轮，每个槽对应一个内核时钟中断间隔（RESET_CYCLES），由本核的时钟中断推进，
+// 定时器在截止时间之后的第一次时钟中断时到期，精度为一个时钟中断间隔
+const TIMER_WHEEL_SLOTS: usize = 64;
+
+struct TimerEntry {
+    id: usize,
+    deadline: usize,
+    waker: Waker,
+}
+
+pub struct TimerWheel {
+    slots: [Vec<TimerEntry>; TIMER_WHEEL_SLOTS],
+    // 下一个要检查的槽对应的时钟中断序号
+    next_tick: usize,
+}
+
+impl TimerWheel {
+    const SLOT_INIT: Vec<TimerEntry> = Vec::new();
+
+    pub const fn new() -> Self {
+        Self { slots: [Self::SLOT_INIT; TIMER_WHEEL_SLOTS], next_tick: 0 }
+    }
+
+    fn insert(&mut self, entry: TimerEntry) {
+        // 向上取整，保证检查该槽时截止时间已过；已经检查过的槽放到下一个要检查的槽中
+        let tick = core::cmp::max(entry.deadline.saturating_add(RESET_CYCLES - 1) / RESET_CYCLES, self.next_tick);
+        self.slots[tick % TIMER_WHEEL_SLOTS].push(entry);
+    }
+
+    fn remove(&mut self, id: usize) {
+        for slot in self.slots.iter_mut() {
+            if let Some(pos) = slot.iter().position(|entry| entry.id == id) {
+                slot.swap_remove(pos);
+                return;
+            }
+        }
+    }
+
+    // 检查从上次推进到now之间的槽，取出到期的定时器，超过一圈的定时器留在槽中
+    fn advance(&mut self, now: usize) -> Vec<Waker> {
+        let now_tick = now / RESET_CYCLES;
+        let mut expired = Vec::new();
+        if now_tick < self.next_tick {
+            return expired;
+        }
+        let count = core::cmp::min(now_tick - self.next_tick + 1, TIMER_WHEEL_SLOTS);
+        for tick in self.next_tick..self.next_tick + count {
+            let slot = &mut self.slots[tick % TIMER_WHEEL_SLOTS];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline <= now {
+                    expired.push(slot.swap_remove(i).waker);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.next_tick = now_tick + 1;
+        expired
+    }
+}
+
+const TIMER_WHEEL_INIT: Mutex<TimerWheel> = Mutex::new(TimerWheel::new());
+static TIMER_WHEELS: [Mutex<TimerWheel>; CONFIG_MAX_NUM_NODES] = [TIMER_WHEEL_INIT; CONFIG_MAX_NUM_NODES];
+static TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+
+// 当前时间（time寄存器的值）
+#[inline]
+pub fn coroutine_now() -> usize {
+    read_time()
+}
+
+// 时钟中断时调用，唤醒本核时间轮中到期的协程
+pub fn async_timer_tick() {
+    let expired = TIMER_WHEELS[cpu_id()].lock().advance(read_time());
+    // 唤醒时不持有时间轮的锁，被唤醒的协程可能在其他核的执行器中
+    for waker in expired {
+        waker.wake();
+    }
+}
+
+// 挂起当前协程直到deadline（time寄存器的值）
+// 定时器注册在第一次poll所在核的时间轮中，future被丢弃（例如协程被取消）时注销
+pub struct Sleep {
+    deadline: usize,
+    // 已注册的定时器（核号, 定时器编号）
+    timer: Option<(usize, usize)>,
+}
+
+#[inline]
+pub fn sleep_until(deadline: usize) -> Sleep {
+    Sleep { deadline, timer: None }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if read_time() >= self.deadline {
+            return Poll::Ready(());
+        }
+        // 被提前唤醒时定时器仍在时间轮中，不需要重复注册
+        if self.timer.is_none() {
+            let cpu = cpu_id();
+            let id = TIMER_ID.fetch_add(1, Ordering::Relaxed);
+            TIMER_WHEELS[cpu].lock().insert(TimerEntry { id, deadline: self.deadline, waker: cx.waker().clone() });
+            self.timer = Some((cpu, id));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((cpu, id)) = self.timer {
+            TIMER_WHEELS[cpu].lock().remove(id);
+        }
+    }
+}
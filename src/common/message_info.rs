@@ -50,6 +50,23 @@ pub enum MessageLabel {
     RISCVASIDPoolAssign,
     RISCVIRQIssueIRQHandlerTrigger,
     nArchInvocationLabels,
+    // 以下为后续增加的用户态中断和异步系统调用相关调用，放在体系结构相关调用之后以保持已有label的编号不变
+    #[cfg(feature = "ENABLE_UINTC")]
+    DomainSetAsyncPolling,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrRegisterFaultRing,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrGetAsyncStats,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrUnregisterSender,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrUnregisterReceiver,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrIssueSender,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrBindSenderTable,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrAddAsyncSubmitter,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Copy, PartialOrd, Ord)]
@@ -72,6 +89,28 @@ pub enum AsyncMessageLabel {
     TCBBindNotification,
     TCBUnbindNotification,
     PutString,
+    TCBReadRegisters,
+    TCBWriteRegisters,
+    TCBCopyRegisters,
+    TCBConfigure,
+    TCBSetPriority,
+    TCBSetMCPriority,
+    TCBSetSchedParams,
+    TCBSetIPCBuffer,
+    TCBSetSpace,
+    TCBSuspend,
+    TCBResume,
+    TCBSetAffinity,
+    TCBSetTLSBase,
+    CNodeSaveCaller,
+    EndpointSend,
+    EndpointNBSend,
+    EndpointCall,
+    EndpointRecv,
+    Cancel,
+    Timeout,
+    UntypedRetypeBatch,
+    EndpointReply,
     UnknownLabel
 }
 
@@ -82,8 +121,9 @@ impl From<AsyncMessageLabel> for u32 {
 }
 
 impl From<u32> for AsyncMessageLabel {
+    // msg_info的高16位为请求的提交者标记，不属于label
     fn from(value: u32) -> Self {
-        match value {
+        match value & 0xffff {
             0 => AsyncMessageLabel::UntypedRetype,
             1 => AsyncMessageLabel::PutChar,
             2 => AsyncMessageLabel::RISCVPageTableMap,
@@ -102,6 +142,28 @@ impl From<u32> for AsyncMessageLabel {
             15 => AsyncMessageLabel::TCBBindNotification,
             16 => AsyncMessageLabel::TCBUnbindNotification,
             17 => AsyncMessageLabel::PutString,
+            18 => AsyncMessageLabel::TCBReadRegisters,
+            19 => AsyncMessageLabel::TCBWriteRegisters,
+            20 => AsyncMessageLabel::TCBCopyRegisters,
+            21 => AsyncMessageLabel::TCBConfigure,
+            22 => AsyncMessageLabel::TCBSetPriority,
+            23 => AsyncMessageLabel::TCBSetMCPriority,
+            24 => AsyncMessageLabel::TCBSetSchedParams,
+            25 => AsyncMessageLabel::TCBSetIPCBuffer,
+            26 => AsyncMessageLabel::TCBSetSpace,
+            27 => AsyncMessageLabel::TCBSuspend,
+            28 => AsyncMessageLabel::TCBResume,
+            29 => AsyncMessageLabel::TCBSetAffinity,
+            30 => AsyncMessageLabel::TCBSetTLSBase,
+            31 => AsyncMessageLabel::CNodeSaveCaller,
+            32 => AsyncMessageLabel::EndpointSend,
+            33 => AsyncMessageLabel::EndpointNBSend,
+            34 => AsyncMessageLabel::EndpointCall,
+            35 => AsyncMessageLabel::EndpointRecv,
+            36 => AsyncMessageLabel::Cancel,
+            37 => AsyncMessageLabel::Timeout,
+            38 => AsyncMessageLabel::UntypedRetypeBatch,
+            39 => AsyncMessageLabel::EndpointReply,
             _ => AsyncMessageLabel::UnknownLabel
         }
     }
@@ -110,7 +172,9 @@ impl From<u32> for AsyncMessageLabel {
 #[derive(Eq, PartialEq, Debug, Clone, Copy, PartialOrd, Ord)]
 pub enum AsyncErrorLabel {
     NoError                       = 0,
-    SyscallError
+    SyscallError,
+    Cancelled,
+    TimedOut
 }
 
 impl From<AsyncErrorLabel> for u16 {
@@ -119,10 +183,18 @@ impl From<AsyncErrorLabel> for u16 {
     }
 }
 
+impl From<AsyncErrorLabel> for u64 {
+    fn from(value: AsyncErrorLabel) -> Self {
+        value as u64
+    }
+}
+
 impl From<u16> for AsyncErrorLabel {
     fn from(value: u16) -> Self {
         match value {
             0 => AsyncErrorLabel:: NoError,
+            2 => AsyncErrorLabel::Cancelled,
+            3 => AsyncErrorLabel::TimedOut,
             _ => AsyncErrorLabel::SyscallError
         }
     }
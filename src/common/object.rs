@@ -11,9 +11,14 @@ pub enum ObjectType {
     NormalPageObject = 6,
     MegaPageObject = 7,
     PageTableObject = 8,
+    #[cfg(feature = "ENABLE_UINTC")]
+    UintrSenderTableObject = 9,
 }
 
+#[cfg(not(feature = "ENABLE_UINTC"))]
 pub const seL4_ObjectTypeCount: usize = ObjectType::PageTableObject as usize + 1;
+#[cfg(feature = "ENABLE_UINTC")]
+pub const seL4_ObjectTypeCount: usize = ObjectType::UintrSenderTableObject as usize + 1;
 
 
 impl ObjectType {
@@ -28,6 +33,8 @@ impl ObjectType {
             ObjectType::NormalPageObject => seL4_PageBits,
             ObjectType::MegaPageObject => seL4_LargePageBits,
             ObjectType::PageTableObject => seL4_PageBits,
+            #[cfg(feature = "ENABLE_UINTC")]
+            ObjectType::UintrSenderTableObject => seL4_UintrSenderTableBits,
         }
     }
 
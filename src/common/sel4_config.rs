@@ -6,6 +6,8 @@ pub const seL4_EndpointBits: usize = 4;
 pub const seL4_NotificationBits: usize = 6;
 pub const seL4_SlotBits: usize = 5;
 pub const seL4_ReplyBits: usize = 4;
+// 用户态中断发送者表：UINTC_ENTRY_NUM个8字节的表项
+pub const seL4_UintrSenderTableBits: usize = 12;
 pub const seL4_MinUntypedBits: usize = 4;
 pub const seL4_MaxUntypedBits: usize = 38;
 
@@ -102,5 +104,8 @@ pub const MessageID_Exception: usize = 1;
 
 pub const seL4_IPCBufferSizeBits: usize = 10;
 
+// 用户态中断的向量数（接收者的pending位数）
+pub const UINTR_VEC_NUM: usize = 64;
+
 
 pub const CONFIG_RESET_CHUNK_BITS: usize = 8;
\ No newline at end of file
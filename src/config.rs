@@ -142,6 +142,8 @@ pub const CYCLES_INTERVAL: usize = 2;
 pub const seL4_MinPrio: usize = 0;
 
 pub const CONFIG_MAX_NUM_WORK_UNITS_PER_PREEMPTION: usize = 100;
+// 启动时作为异步系统调用轮询核的hart（按位），0号核不能作为轮询核
+pub const CONFIG_ASYNC_POLLING_HARTS: usize = 0;
 pub const CONFIG_RETYPE_FAN_OUT_LIMIT: usize = 256;
 
 
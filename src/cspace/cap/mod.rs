@@ -39,6 +39,8 @@ pub enum CapTag {
     CapIrqHandlerCap = 16,
     CapZombieCap = 18,
     CapDomainCap = 20,
+    CapUintrSenderCap = 22,
+    CapUintrSenderTableCap = 24,
     CapFrameCap = 1,
     CapPageTableCap = 3,
     CapASIDControlCap = 11,
@@ -93,6 +95,13 @@ plus_define_bitfield! {
             capZombieType, get_zombie_type, set_zombie_type, 0, 0, 7, 0, false
         },
         new_domain_cap, CapTag::CapDomainCap as usize => {},
+        new_uintr_sender_cap, CapTag::CapUintrSenderCap as usize => {
+            capUintrSenderVec, get_uintr_sender_vec, set_uintr_sender_vec, 1, 0, 16, 0, false,
+            capUintrSenderNtfnPtr, get_uintr_sender_ntfn_ptr, set_uintr_sender_ntfn_ptr, 0, 0, 39, 0, true
+        },
+        new_uintr_sender_table_cap, CapTag::CapUintrSenderTableCap as usize => {
+            capUintrSenderTablePtr, get_uintr_sender_table_ptr, set_uintr_sender_table_ptr, 0, 0, 39, 0, true
+        },
         new_frame_cap, CapTag::CapFrameCap as usize => {
             capFMappedASID, get_frame_mapped_asid, set_frame_mapped_asid, 1, 48, 16, 0, false,
             capFBasePtr, get_frame_base_ptr, set_frame_base_ptr, 1, 9, 39, 0, true,
@@ -142,6 +151,17 @@ impl cap_t {
                 }
             }
 
+            // 发送权限的中断向量与notification的badge一样只能设置一次
+            CapTag::CapUintrSenderCap => {
+                if !preserve && self.get_uintr_sender_vec() == 0 && new_data < UINTR_VEC_NUM {
+                    let mut new_cap = self.clone();
+                    new_cap.set_uintr_sender_vec(new_data);
+                    new_cap
+                } else {
+                    cap_t::new_null_cap()
+                }
+            }
+
             CapTag::CapCNodeCap => {
                 let w = CNodeCapData::new(new_data);
                 let guard_size = w.get_guard_size();
@@ -175,6 +195,7 @@ impl cap_t {
             CapTag::CapFrameCap => self.get_frame_base_ptr(),
             CapTag::CapPageTableCap => self.get_pt_base_ptr(),
             CapTag::CapASIDPoolCap => self.get_asid_pool(),
+            CapTag::CapUintrSenderTableCap => self.get_uintr_sender_table_ptr(),
             _ => {
                 0
             }
@@ -189,6 +210,7 @@ impl cap_t {
             CapTag::CapCNodeCap => self.get_cnode_radix() + seL4_SlotBits,
             CapTag::CapPageTableCap => PT_SIZE_BITS,
             CapTag::CapReplyCap => seL4_ReplyBits,
+            CapTag::CapUintrSenderTableCap => seL4_UintrSenderTableBits,
             _ => 0,
         }
     }
@@ -196,7 +218,7 @@ impl cap_t {
     pub fn get_cap_is_physical(&self) -> bool {
         match self.get_cap_type() {
             CapTag::CapUntypedCap | CapTag::CapEndpointCap | CapTag::CapNotificationCap | CapTag::CapCNodeCap | CapTag::CapFrameCap | CapTag::CapASIDPoolCap |
-            CapTag::CapPageTableCap | CapTag::CapZombieCap | CapTag::CapThreadCap => true,
+            CapTag::CapPageTableCap | CapTag::CapZombieCap | CapTag::CapThreadCap | CapTag::CapUintrSenderTableCap => true,
             _ => false,
         }
     }
@@ -230,8 +252,22 @@ pub fn same_region_as(cap1: &cap_t, cap2: &cap_t) -> bool {
             }
             false 
         }
-        CapTag::CapEndpointCap | CapTag::CapNotificationCap | CapTag::CapPageTableCap | CapTag::CapASIDPoolCap 
-            | CapTag::CapThreadCap => {
+        CapTag::CapNotificationCap => {
+            match cap2.get_cap_type() {
+                CapTag::CapNotificationCap => cap1.get_nf_ptr() == cap2.get_nf_ptr(),
+                // 从notification派生的发送权限
+                CapTag::CapUintrSenderCap => cap1.get_nf_ptr() == cap2.get_uintr_sender_ntfn_ptr(),
+                _ => false
+            }
+        }
+        CapTag::CapUintrSenderCap => {
+            if cap2.get_cap_type() == CapTag::CapUintrSenderCap {
+                return cap1.get_uintr_sender_ntfn_ptr() == cap2.get_uintr_sender_ntfn_ptr();
+            }
+            false
+        }
+        CapTag::CapEndpointCap | CapTag::CapPageTableCap | CapTag::CapASIDPoolCap 
+            | CapTag::CapThreadCap | CapTag::CapUintrSenderTableCap => {
             if cap2.get_cap_type() == cap1.get_cap_type() {
                 return cap1.get_cap_ptr() == cap2.get_cap_ptr();
             }
@@ -278,6 +314,14 @@ pub fn same_object_as(cap1: &cap_t, cap2: &cap_t) -> bool {
     if cap1.get_cap_type() == CapTag::CapIrqControlCap && cap2.get_cap_type() == CapTag::CapIrqHandlerCap {
         return false;
     }
+    if cap1.get_cap_type() == CapTag::CapNotificationCap && cap2.get_cap_type() == CapTag::CapUintrSenderCap {
+        return false;
+    }
+    // 同一notification、同一向量的发送权限为同一对象
+    if cap1.get_cap_type() == CapTag::CapUintrSenderCap && cap2.get_cap_type() == CapTag::CapUintrSenderCap {
+        return cap1.get_uintr_sender_ntfn_ptr() == cap2.get_uintr_sender_ntfn_ptr()
+            && cap1.get_uintr_sender_vec() == cap2.get_uintr_sender_vec();
+    }
     if cap1.isArchCap() && cap2.isArchCap() {
         return arch_same_object_as(cap1, cap2);
     }
@@ -313,6 +357,14 @@ pub fn is_cap_revocable(derived_cap: &cap_t, src_cap: &cap_t) -> bool {
             return src_cap.get_cap_type() == CapTag::CapIrqControlCap;
         }
 
+        CapTag::CapUintrSenderCap => {
+            if src_cap.get_cap_type() == CapTag::CapNotificationCap {
+                return true;
+            }
+            assert_eq!(src_cap.get_cap_type(), CapTag::CapUintrSenderCap);
+            return derived_cap.get_uintr_sender_vec() != src_cap.get_uintr_sender_vec();
+        }
+
         CapTag::CapUntypedCap => {
             return true;
         }
@@ -125,14 +125,26 @@ impl cte_t {
                     !(next.cteMDBNode.get_first_badged() != 0);
             }
             CapTag::CapNotificationCap => {
-                assert_eq!(next.cap.get_cap_type(), CapTag::CapNotificationCap);
                 let badge = self.cap.get_nf_badge();
+                if next.cap.get_cap_type() == CapTag::CapUintrSenderCap {
+                    // 发送权限的向量为派生时notification的badge
+                    return badge == 0 || badge == next.cap.get_uintr_sender_vec();
+                }
+                assert_eq!(next.cap.get_cap_type(), CapTag::CapNotificationCap);
                 if badge == 0 {
                     return true;
                 }
                 return badge == next.cap.get_nf_badge() &&
                     !(next.cteMDBNode.get_first_badged() != 0);
             }
+            CapTag::CapUintrSenderCap => {
+                let vec = self.cap.get_uintr_sender_vec();
+                if vec == 0 {
+                    return true;
+                }
+                return vec == next.cap.get_uintr_sender_vec() &&
+                    !(next.cteMDBNode.get_first_badged() != 0);
+            }
             _ => true
         }
     }
@@ -11,6 +11,10 @@ pub fn Arch_finaliseCap(cap: &cap_t, final_: bool) -> finaliseCap_ret {
     let mut fc_ret = finaliseCap_ret::default();
     match cap.get_cap_type() {
         CapTag::CapFrameCap => {
+            #[cfg(feature = "ENABLE_UINTC")]
+            if final_ {
+                crate::async_runtime::async_syscall_release_buffer(cap.get_frame_base_ptr());
+            }
             if cap.get_frame_mapped_asid() != 0 {
                 match unmapPage(cap.get_frame_size(), cap.get_frame_mapped_asid(), 
                     cap.get_frame_mapped_address(), cap.get_frame_base_ptr()) {
@@ -77,6 +81,12 @@ pub fn finaliseCap(cap: &cap_t, _final: bool, _exposed: bool) -> finaliseCap_ret
         CapTag::CapNotificationCap => {
             if _final {
                 let ntfn =  convert_to_mut_type_ref::<notification_t>(cap.get_nf_ptr());
+                // 同时释放异步系统调用的注册
+                #[cfg(feature = "ENABLE_UINTC")]
+                {
+                    crate::uintc::unregister_receiver(ntfn);
+                    crate::async_runtime::async_syscall_release_ntfn(ntfn.get_ptr());
+                }
                 ntfn.safe_unbind_tcb();
                 ntfn.cancel_call_signal();
             }
@@ -84,6 +94,26 @@ pub fn finaliseCap(cap: &cap_t, _final: bool, _exposed: bool) -> finaliseCap_ret
             fc_ret.cleanupInfo = cap_t::new_null_cap();
             return fc_ret;
         }
+        CapTag::CapUintrSenderCap => {
+            // 最后一个发送权限被删除时，无效化通过它注册的发送者表项
+            #[cfg(feature = "ENABLE_UINTC")]
+            if _final {
+                crate::uintc::release_sender_cap(cap.get_uintr_sender_ntfn_ptr(), cap.get_uintr_sender_vec());
+            }
+            fc_ret.remainder = cap_t::new_null_cap();
+            fc_ret.cleanupInfo = cap_t::new_null_cap();
+            return fc_ret;
+        }
+        CapTag::CapUintrSenderTableCap => {
+            // 发送者表对象被删除时释放其中的表项并与线程解绑
+            #[cfg(feature = "ENABLE_UINTC")]
+            if _final {
+                crate::uintc::release_sender_table_object(cap.get_uintr_sender_table_ptr());
+            }
+            fc_ret.remainder = cap_t::new_null_cap();
+            fc_ret.cleanupInfo = cap_t::new_null_cap();
+            return fc_ret;
+        }
         CapTag::CapReplyCap | CapTag::CapNullCap | CapTag::CapDomainCap => {
             fc_ret.remainder = cap_t::new_null_cap();
             fc_ret.cleanupInfo = cap_t::new_null_cap();
@@ -120,6 +150,11 @@ pub fn finaliseCap(cap: &cap_t, _final: bool, _exposed: bool) -> finaliseCap_ret
                     crate::deps::remoteTCBStall(tcb)
                 };
                 let cte_ptr = tcb.get_cspace_mut_ref(tcbCTable);
+                #[cfg(feature = "ENABLE_UINTC")]
+                {
+                    crate::async_runtime::async_syscall_release_tcb(tcb);
+                    crate::uintc::release_sender_table(tcb);
+                }
                 safe_unbind_notification(tcb);
                 tcb.cancel_ipc();
                 tcb.suspend();
@@ -3,17 +3,15 @@ use crate::common::structures::exception_t;
 use crate::cspace::interface::CapTag;
 use log::debug;
 use riscv::register::scause;
-use crate::async_runtime::{coroutine_run_until_blocked, coroutine_wake, IPCItem, NEW_BUFFER_MAP, NewBuffer};
+use crate::async_runtime::{coroutine_run_until_blocked, AsyncWakeSource, coroutine_wake, async_syscall_notify_tick, async_timer_tick, IPCItem, NEW_BUFFER_MAP, NewBuffer};
 use crate::boot::cpu_prio;
 use crate::task_manager::{activateThread, get_currenct_thread, get_idle_thread, schedule, tcb_t, timerTick};
 use crate::task_manager::ipc::notification_t;
 use crate::config::{irqInvalid, maxIRQ};
 use crate::interrupt::*;
 use crate::riscv::resetTimer;
-use crate::uintc::{KERNEL_SENDER_POOL_IDX, NET_UINTR_IDX, UIntrReceiver, UIntrSTEntry};
-use crate::uintr;
+use crate::uintc::{NET_UINTR_IDX, UIntrReceiver};
 use crate::uintr::uipi_send;
-use crate::vspace::kpptr_to_paddr;
 use core::sync::atomic::Ordering::SeqCst;
 use crate::common::utils::convert_to_option_mut_type_ref;
 use crate::config::IRQConst::INTERRUPT_IPI_2;
@@ -53,10 +51,8 @@ static NET_INTR_THRESHOLD: usize = 3;
 static SECOND_TIMER: usize = 50;
 static mut SECOND_TIMER_CNT: usize = 0;
 pub unsafe fn send_net_uintr() {
-    let uist_idx = *KERNEL_SENDER_POOL_IDX.lock();
     let offset = *NET_UINTR_IDX.lock();
-    let frame_addr = crate::uintc::UINTR_ST_POOL.as_ptr().offset((uist_idx * core::mem::size_of::<UIntrSTEntry>() * crate::uintc::config::UINTC_ENTRY_NUM) as isize) as usize;
-    uintr::suist::write((1 << 63) | (1 << 44) | (kpptr_to_paddr(frame_addr) >> 0xC));
+    crate::uintc::kernel_uist_activate();
     uipi_send(offset);
 }
 
@@ -90,24 +86,27 @@ pub fn handleInterrupt(irq: usize) {
                 && handler_cap.get_nf_can_send() != 0 {
                 let ntfn = convert_to_mut_type_ref::<notification_t>(handler_cap.get_nf_ptr());
                 if let Some(tcb) = convert_to_option_mut_type_ref::<tcb_t>(ntfn.get_bound_tcb()) {
-                    if let Some(cid) = tcb.asyncSysHandlerCid {
-                        for item in unsafe { &mut NEW_BUFFER_MAP } {
-                            if item.cid == cid {
-                                let new_buffer = &mut item.buf;
-                                let mut item = IPCItem::default();
-                                item.msg_info = 1;
-                                new_buffer.req_items.write_free_item(&item).unwrap();
-                                if new_buffer.recv_req_status.load(SeqCst) == false {
-                                    NET_INTR_CNT += 1;
-                                    new_buffer.recv_req_status.store(true, SeqCst);
-                                    send_net_uintr();
-                                    // debug!("NET INTR CNT: {}", NET_INTR_CNT);
-                                }
-                                break;
+                    // 外设中断通知作为cid为0的响应写入该线程注册的第一个ring。
+                    // req_items的生产者是用户态，内核不能再向其中写入；res_items的生产者只有内核，
+                    // 中断处理与处理协程都持有内核锁，不会同时写入
+                    match unsafe { NEW_BUFFER_MAP.iter().find(|map| map.tcb == tcb.get_ptr()) } {
+                        Some(map) => {
+                            let new_buffer = &map.buf;
+                            let mut item = IPCItem::default();
+                            item.msg_info = 1;
+                            if new_buffer.push_response(&item).is_err() {
+                                debug!("handleInterrupt: response queue of ring {:#x} is full", new_buffer.get_ptr());
                             }
+                            if new_buffer.recv_reply_status().load(SeqCst) == false {
+                                NET_INTR_CNT += 1;
+                                new_buffer.recv_reply_status().store(true, SeqCst);
+                                send_net_uintr();
+                                // debug!("NET INTR CNT: {}", NET_INTR_CNT);
+                            }
+                        }
+                        None => {
+                            send_net_uintr();
                         }
-                    } else {
-                        send_net_uintr();
                     }
                 }
                 // NET_INTR_CNT += 1;
@@ -131,8 +130,13 @@ pub fn handleInterrupt(irq: usize) {
             //     // debug!("wake cid: {}", item.cid.0);
             //     // coroutine_wake(&item.cid);
             // }
+            async_timer_tick();
             if get_currenct_thread().get_ptr() != get_idle_thread().get_ptr() {
-                coroutine_run_until_blocked();
+                coroutine_run_until_blocked(AsyncWakeSource::Timer);
+            }
+            async_syscall_notify_tick();
+            if cpu_id() == 0 {
+                crate::uintc::uintr_deliver_pending();
             }
             timerTick();
             resetTimer();
@@ -141,7 +145,7 @@ pub fn handleInterrupt(irq: usize) {
         IRQState::IRQIPI => {
             if irq == INTERRUPT_IPI_2 as usize {
                 // debug!("handle coroutine run");
-                coroutine_run_until_blocked();
+                coroutine_run_until_blocked(AsyncWakeSource::Ipi);
             } else {
                 unsafe { crate::deps::handleIPI(irq, true) };
             }
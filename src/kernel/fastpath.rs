@@ -305,6 +305,8 @@ pub fn fastpath_reply_recv(cptr: usize, msgInfo: usize) {
         ep.set_queue_head(current.get_ptr());
     }
     endpoint_ptr_mset_epQueue_tail_state(ep as *mut endpoint_t, get_currenct_thread().get_ptr(), EPState_Recv);
+    #[cfg(feature = "ENABLE_UINTC")]
+    crate::async_runtime::async_endpoint_wake(ep.get_ptr());
 
     let node = convert_to_mut_type_ref::<cte_t>(caller_slot.cteMDBNode.get_prev());
     mdb_node_ptr_mset_mdbNext_mdbRevocable_mdbFirstBadged(&mut node.cteMDBNode, 0, 1, 1);
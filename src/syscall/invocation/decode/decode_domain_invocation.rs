@@ -8,6 +8,10 @@ use crate::task_manager::{set_thread_state, get_currenct_thread, ThreadState, tc
 use crate::{kernel::boot::{current_syscall_error, get_extra_cap_by_index}, syscall::get_syscall_arg};
 
 pub fn decode_domain_invocation(invLabel: MessageLabel, length: usize, buffer: Option<&seL4_IPCBuffer>) -> exception_t {
+    #[cfg(feature = "ENABLE_UINTC")]
+    if invLabel == MessageLabel::DomainSetAsyncPolling {
+        return decode_set_async_polling(length, buffer);
+    }
     if invLabel != MessageLabel::DomainSetSet {
         unsafe { current_syscall_error._type = seL4_IllegalOperation; }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
@@ -44,4 +48,27 @@ pub fn decode_domain_invocation(invLabel: MessageLabel, length: usize, buffer: O
     set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
     convert_to_mut_type_ref::<tcb_t>(thread_cap.get_tcb_ptr()).set_domain(domain);
     exception_t::EXCEPTION_NONE
-}
\ No newline at end of file
+}
+
+// 设置或取消异步系统调用轮询核，参数为核号和是否轮询
+#[cfg(feature = "ENABLE_UINTC")]
+fn decode_set_async_polling(length: usize, buffer: Option<&seL4_IPCBuffer>) -> exception_t {
+    use crate::async_runtime::{async_polling_valid_hart, async_polling_set};
+    if length < 2 {
+        debug!("Domain SetAsyncPolling: Truncated message.");
+        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let cpu = get_syscall_arg(0, buffer);
+    if !async_polling_valid_hart(cpu) {
+        debug!("Domain SetAsyncPolling: invalid hart {}.", cpu);
+        unsafe {
+            current_syscall_error._type = seL4_InvalidArgument;
+            current_syscall_error.invalidArgumentNumber = 0;
+        }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+    async_polling_set(cpu, get_syscall_arg(1, buffer) != 0);
+    exception_t::EXCEPTION_NONE
+}
@@ -471,6 +471,12 @@ fn decode_set_affinity(cap: &cap_t, length: usize, buffer: Option<&seL4_IPCBuffe
         unsafe { current_syscall_error._type = seL4_IllegalOperation; }
         return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
+    #[cfg(feature = "ENABLE_UINTC")]
+    if crate::async_runtime::async_polling_on(affinity) {
+        debug!("TCB SetAffinity: Requested CPU is reserved for async syscall polling.");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
     set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
     let tcb = convert_to_mut_type_ref::<tcb_t>(cap.get_tcb_ptr());
     invoke_tcb_set_affinity(tcb, affinity)
@@ -488,7 +494,7 @@ fn decode_set_tls_base(cap: &cap_t, length: usize, buffer: Option<&seL4_IPCBuffe
 }
 
 #[inline]
-fn decode_set_space_args(root_data: usize, root_cap: cap_t, root_slot: &mut cte_t) -> Result<cap_t, exception_t> {
+pub fn decode_set_space_args(root_data: usize, root_cap: cap_t, root_slot: &mut cte_t) -> Result<cap_t, exception_t> {
     let mut ret_root_cap = root_cap;
     if root_data != 0 {
         ret_root_cap = root_cap.update_data(false, root_data);
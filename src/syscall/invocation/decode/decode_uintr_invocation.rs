@@ -0,0 +1,111 @@
+use crate::common::{message_info::{MessageLabel, seL4_MessageInfo_t}, structures::{seL4_IPCBuffer, exception_t}, sel4_config::*, utils::convert_to_mut_type_ref};
+use crate::cspace::interface::{cte_t, cap_t, CapTag, cte_insert};
+use log::debug;
+use crate::task_manager::{set_thread_state, get_currenct_thread, ThreadState, tcb_t, badgeRegister, msgInfoRegister};
+
+use crate::{kernel::boot::{get_extra_cap_by_index, current_syscall_error}, syscall::{get_syscall_arg, lookupSlotForCNodeOp}};
+
+// 从notification派生发送权限，中断向量为notification的badge
+// 第0个extra cap为目标CNode，第0、1个参数为目标slot的index和depth
+pub fn decode_uintr_issue_sender(length: usize, src_slot: &mut cte_t, cap: &cap_t, buffer: Option<&seL4_IPCBuffer>) -> exception_t {
+    if length < 2 || get_extra_cap_by_index(0).is_none() {
+        debug!("UintrIssueSender: Truncated message.");
+        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let index = get_syscall_arg(0, buffer);
+    let depth = get_syscall_arg(1, buffer);
+    let cnode_cap = get_extra_cap_by_index(0).unwrap().cap;
+    if cap.get_nf_can_send() == 0 {
+        debug!("UintrIssueSender: notification cap without send right.");
+        unsafe {
+            current_syscall_error._type = seL4_InvalidCapability;
+            current_syscall_error.invalidCapNumber = 0;
+        }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let vec = cap.get_nf_badge();
+    if vec >= UINTR_VEC_NUM {
+        debug!("UintrIssueSender: badge {} is not a valid vector.", vec);
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let lu_ret = lookupSlotForCNodeOp(false, &cnode_cap, index, depth);
+    if lu_ret.status != exception_t::EXCEPTION_NONE {
+        debug!("UintrIssueSender: target slot invalid.");
+        return lu_ret.status;
+    }
+    let dest_slot = convert_to_mut_type_ref::<cte_t>(lu_ret.slot as usize);
+    if dest_slot.cap.get_cap_type() != CapTag::CapNullCap {
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+        debug!("UintrIssueSender: target slot not empty.");
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+    cte_insert(&cap_t::new_uintr_sender_cap(vec, cap.get_nf_ptr()), src_slot, dest_slot);
+    exception_t::EXCEPTION_NONE
+}
+
+// 发送权限上的调用：UintrRegisterSender在当前线程的发送者表中注册表项并返回其下标，
+// UintrUnregisterSender注销当前线程通过该权限注册的表项
+pub fn decode_uintr_sender_invocation(label: MessageLabel, cap: &cap_t, call: bool) -> exception_t {
+    match label {
+        MessageLabel::UintrRegisterSender => {
+            let offset = match crate::uintc::register_sender_cap(cap) {
+                Ok(offset) => offset,
+                Err(status) => return status,
+            };
+            if call {
+                let thread = get_currenct_thread();
+                thread.set_register(badgeRegister, 0);
+                let length = thread.set_mr(0, offset);
+                thread.set_register(msgInfoRegister, seL4_MessageInfo_t::new(0, 0, 0, length).to_word());
+            }
+            set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+            exception_t::EXCEPTION_NONE
+        }
+        MessageLabel::UintrUnregisterSender => {
+            if !crate::uintc::unregister_sender(get_currenct_thread(), cap.get_uintr_sender_ntfn_ptr(), Some(cap.get_uintr_sender_vec())) {
+                debug!("UintrUnregisterSender: not registered with this sender cap.");
+                unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+                return exception_t::EXCEPTION_SYSCALL_ERROR;
+            }
+            set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+            exception_t::EXCEPTION_NONE
+        }
+        _ => {
+            debug!("UintrSender: illegal operation attempted.");
+            unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+            exception_t::EXCEPTION_SYSCALL_ERROR
+        }
+    }
+}
+
+// 发送者表上的调用：UintrBindSenderTable将发送者表绑定到第0个extra cap对应的线程
+pub fn decode_uintr_sender_table_invocation(label: MessageLabel, cap: &cap_t) -> exception_t {
+    if label != MessageLabel::UintrBindSenderTable {
+        debug!("UintrSenderTable: illegal operation attempted.");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    if get_extra_cap_by_index(0).is_none() {
+        debug!("UintrBindSenderTable: Truncated message.");
+        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let tcb_cap = get_extra_cap_by_index(0).unwrap().cap;
+    if tcb_cap.get_cap_type() != CapTag::CapThreadCap {
+        debug!("UintrBindSenderTable: invalid thread cap.");
+        unsafe {
+            current_syscall_error._type = seL4_InvalidCapability;
+            current_syscall_error.invalidCapNumber = 1;
+        }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let status = crate::uintc::bind_sender_table(cap.get_uintr_sender_table_ptr(), convert_to_mut_type_ref::<tcb_t>(tcb_cap.get_tcb_ptr()));
+    if status != exception_t::EXCEPTION_NONE {
+        return status;
+    }
+    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+    exception_t::EXCEPTION_NONE
+}
@@ -1,23 +1,27 @@
-mod decode_tcb_invocation;
+pub mod decode_tcb_invocation;
 mod decode_domain_invocation;
 mod decode_cnode_invocation;
 pub mod decode_untyped_invocation;
 mod decode_mmu_invocation;
 pub mod decode_irq_invocation;
+#[cfg(feature = "ENABLE_UINTC")]
+mod decode_uintr_invocation;
 
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::intrinsics::unlikely;
+use crate::BIT;
 
-use crate::common::{structures::{exception_t, seL4_IPCBuffer}, sel4_config::seL4_InvalidCapability, utils::convert_to_mut_type_ref, message_info::MessageLabel};
+use crate::common::{structures::{exception_t, seL4_IPCBuffer}, sel4_config::seL4_InvalidCapability, utils::{convert_to_mut_type_ref, pageBitsForSize}, message_info::{MessageLabel, seL4_MessageInfo_t}};
 use crate::cspace::interface::{cte_t, cap_t, CapTag};
 use crate::task_manager::ipc::{endpoint_t, notification_t};
 use log::debug;
-use crate::async_runtime::NewBuffer;
-use crate::common::sel4_config::seL4_TruncatedMessage;
-use crate::task_manager::{set_thread_state, get_currenct_thread, ThreadState, tcb_t};
+use crate::common::sel4_config::{seL4_TruncatedMessage, seL4_InvalidArgument, seL4_IllegalOperation};
+use crate::task_manager::{set_thread_state, get_currenct_thread, ThreadState, tcb_t, badgeRegister, msgInfoRegister};
 
 use crate::kernel::boot::{current_syscall_error, get_extra_cap_by_index};
 use crate::syscall::invocation::decode::decode_irq_invocation::decode_irq_handler_invocation;
+use crate::syscall::utils::get_syscall_arg;
 
 use self::{
     decode_tcb_invocation::decode_tcb_invocation,
@@ -71,17 +75,44 @@ pub fn decode_invocation(label: MessageLabel, length: usize, slot: &mut cte_t, c
                         return exception_t::EXCEPTION_SYSCALL_ERROR;
                     }
                     let tcb_cap = tcb_slot.unwrap().cap;
-                    crate::uintc::register_receiver(convert_to_mut_type_ref::<notification_t>(cap.get_nf_ptr()), convert_to_mut_type_ref::<tcb_t>(tcb_cap.get_tcb_ptr()));
+                    let status = crate::uintc::register_receiver(convert_to_mut_type_ref::<notification_t>(cap.get_nf_ptr()), convert_to_mut_type_ref::<tcb_t>(tcb_cap.get_tcb_ptr()));
+                    if status != exception_t::EXCEPTION_NONE {
+                        return status;
+                    }
                     set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
                     return exception_t::EXCEPTION_NONE;
                 } else if label == MessageLabel::UintrRegisterSender {
-                    crate::uintc::register_sender(cap);
+                    let status = crate::uintc::register_sender(cap);
+                    if status != exception_t::EXCEPTION_NONE {
+                        return status;
+                    }
+                    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+                    return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrUnregisterSender {
+                    // 注销当前线程以该notification为目标的发送者表项
+                    if !crate::uintc::unregister_sender(get_currenct_thread(), cap.get_nf_ptr(), None) {
+                        debug!("UInt UnregisterSender: not registered as a sender.");
+                        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
                     set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
                     return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrUnregisterReceiver {
+                    let ntfn = convert_to_mut_type_ref::<notification_t>(cap.get_nf_ptr());
+                    if ntfn.get_uintr_flag() != 1 {
+                        debug!("UInt UnregisterReceiver: not registered as a receiver.");
+                        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    crate::uintc::unregister_receiver(ntfn);
+                    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+                    return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrIssueSender {
+                    return decode_uintr_invocation::decode_uintr_issue_sender(length, slot, cap, buffer);
                 } else if label == MessageLabel::UintrRegisterAsyncSyscall {
                     use crate::async_runtime::async_syscall_handler;
                     use crate::async_runtime::NEW_BUFFER_MAP;
-                    use crate::async_runtime::{coroutine_spawn, NewBufferMap};
+                    use crate::async_runtime::{coroutine_spawn_with_owner, CoroutineId, NewBufferMap, AsyncRing, AsyncItemVersion, AsyncNotifyPolicy, AsyncNotifyState, AsyncRingStats, async_polling_active, async_syscall_vspace_of};
                     let new_buffer_slot = get_extra_cap_by_index(0);
                     if new_buffer_slot.is_none() {
                         debug!("UInt RegisterAsyncSyscall: Truncated message.");
@@ -90,18 +121,180 @@ pub fn decode_invocation(label: MessageLabel, length: usize, slot: &mut cte_t, c
                     }
                     debug!("UintrRegisterAsyncSyscall: Enter");
                     let new_buffer_cap = new_buffer_slot.unwrap().cap;
+                    // 协商item布局版本，消息第0个参数为用户态支持的最高版本
+                    let version = AsyncItemVersion::negotiate(if length > 0 { get_syscall_arg(0, buffer) } else { 0 });
+                    // ring必须完整地落在frame内，V2的ring远大于4K页
+                    if new_buffer_cap.get_cap_type() != CapTag::CapFrameCap
+                        || BIT!(pageBitsForSize(new_buffer_cap.get_frame_size())) < version.ring_size() {
+                        debug!("UintrRegisterAsyncSyscall: frame too small for a version {:?} ring.", version);
+                        unsafe {
+                            current_syscall_error._type = seL4_InvalidCapability;
+                            current_syscall_error.invalidCapNumber = 1;
+                        }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    let ring = AsyncRing::new(new_buffer_cap.get_frame_base_ptr(), version);
+                    // 第1、2个参数为完成通知的合并策略和策略参数，缺省时每个响应立即通知
+                    let policy = match AsyncNotifyPolicy::from_args(if length > 1 { get_syscall_arg(1, buffer) } else { 0 },
+                        if length > 2 { get_syscall_arg(2, buffer) } else { 0 }) {
+                        Ok(policy) => policy,
+                        Err(arg) => {
+                            debug!("UintrRegisterAsyncSyscall: invalid notify policy.");
+                            unsafe {
+                                current_syscall_error._type = seL4_InvalidArgument;
+                                current_syscall_error.invalidArgumentNumber = arg;
+                            }
+                            return exception_t::EXCEPTION_SYSCALL_ERROR;
+                        }
+                    };
+                    // 第3个参数的第0位表示ring由注册线程所在地址空间中的所有线程共享
+                    let vspace = if length > 3 && get_syscall_arg(3, buffer) & 1 != 0 {
+                        match async_syscall_vspace_of(get_currenct_thread()) {
+                            Some(vspace) => Some(vspace),
+                            None => {
+                                debug!("UintrRegisterAsyncSyscall: shared ring without a valid vspace.");
+                                unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+                                return exception_t::EXCEPTION_SYSCALL_ERROR;
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    if async_polling_active() {
+                        // 有轮询核时用户态不需要唤醒处理协程
+                        ring.recv_req_status().store(true, core::sync::atomic::Ordering::SeqCst);
+                    }
                     //注册发送端，获取sender_id
                     let sender_id = crate::uintc::register_sender_async_syscall(cap);
                     debug!("UintrRegisterAsyncSyscall: sender id = {:?}", sender_id);
-                    //生成异步系统调用处理协程并将cid保存至tcb
-                    let cid = coroutine_spawn(Box::pin(async_syscall_handler(*cap, new_buffer_cap, get_currenct_thread(), sender_id as usize)));
-                    get_currenct_thread().asyncSysHandlerCid = Some(cid);
-                    debug!("UintrRegisterAsyncSyscall: coroutine id = {:?}", cid);
+                    let mut map = Box::new(NewBufferMap {
+                        buf: ring,
+                        cid: CoroutineId(0),
+                        tcb: get_currenct_thread().get_ptr(),
+                        ntfn: cap.get_nf_ptr(),
+                        sender_id: if sender_id < 0 { None } else { Some(sender_id as usize) },
+                        notify: AsyncNotifyState::new(policy),
+                        vspace,
+                        submitters: Vec::new(),
+                        stats: AsyncRingStats::new(),
+                    });
+                    //生成异步系统调用处理协程，一个线程可以注册多个ring，每个ring有独立的处理协程
+                    map.cid = coroutine_spawn_with_owner(Box::pin(async_syscall_handler(*cap, map.get_ptr(), get_currenct_thread())),
+                        get_currenct_thread().get_ptr());
+                    debug!("UintrRegisterAsyncSyscall: coroutine id = {:?}", map.cid);
                     unsafe {
-                        NEW_BUFFER_MAP.push(NewBufferMap {
-                            buf: &mut *(new_buffer_cap.get_frame_base_ptr() as *mut NewBuffer),
-                            cid,
-                        })
+                        NEW_BUFFER_MAP.push(map);
+                    }
+                    if call {
+                        reply_words(get_currenct_thread(), &[version as usize]);
+                        return exception_t::EXCEPTION_NONE;
+                    }
+                    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+                    return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrRegisterFaultRing {
+                    // 第0、1个extra cap为fault ring所在的frame和fault线程，第0个参数为该线程在ring中的badge
+                    let (ring_slot, tcb_slot) = (get_extra_cap_by_index(0), get_extra_cap_by_index(1));
+                    if length < 1 || ring_slot.is_none() || tcb_slot.is_none() {
+                        debug!("UInt RegisterFaultRing: Truncated message.");
+                        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    let ring_cap = ring_slot.unwrap().cap;
+                    let tcb_cap = tcb_slot.unwrap().cap;
+                    if ring_cap.get_cap_type() != CapTag::CapFrameCap || tcb_cap.get_cap_type() != CapTag::CapThreadCap {
+                        debug!("UInt RegisterFaultRing: invalid frame or thread cap.");
+                        unsafe {
+                            current_syscall_error._type = seL4_InvalidCapability;
+                            current_syscall_error.invalidCapNumber = if ring_cap.get_cap_type() != CapTag::CapFrameCap { 1 } else { 2 };
+                        }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    if BIT!(pageBitsForSize(ring_cap.get_frame_size())) < crate::async_runtime::FAULT_RING_SIZE {
+                        debug!("UInt RegisterFaultRing: frame too small for a fault ring.");
+                        unsafe {
+                            current_syscall_error._type = seL4_InvalidCapability;
+                            current_syscall_error.invalidCapNumber = 1;
+                        }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    if crate::async_runtime::async_fault_ring_register(cap, ring_cap.get_frame_base_ptr(), convert_to_mut_type_ref::<tcb_t>(tcb_cap.get_tcb_ptr()),
+                        get_syscall_arg(0, buffer), get_currenct_thread()).is_err() {
+                        unsafe {
+                            current_syscall_error._type = seL4_InvalidArgument;
+                            current_syscall_error.invalidArgumentNumber = 0;
+                        }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+                    return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrGetAsyncStats {
+                    // 第0个参数为0时读取通过该notification注册的第index个ring的统计，为1时读取index号核的执行器的统计
+                    // 第1个参数为index，第2个参数的第0位表示读取后清零
+                    if length < 2 {
+                        debug!("UintrGetAsyncStats: Truncated message.");
+                        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    let index = get_syscall_arg(1, buffer);
+                    let reset = length > 2 && get_syscall_arg(2, buffer) & 1 != 0;
+                    let words = match get_syscall_arg(0, buffer) {
+                        0 => crate::async_runtime::async_ring_stats(cap.get_nf_ptr(), index, reset),
+                        1 => crate::async_runtime::async_executor_stats(index, reset),
+                        _ => {
+                            debug!("UintrGetAsyncStats: invalid stats kind.");
+                            unsafe {
+                                current_syscall_error._type = seL4_InvalidArgument;
+                                current_syscall_error.invalidArgumentNumber = 0;
+                            }
+                            return exception_t::EXCEPTION_SYSCALL_ERROR;
+                        }
+                    };
+                    let words = match words {
+                        Some(words) => words,
+                        None => {
+                            debug!("UintrGetAsyncStats: invalid index {}.", index);
+                            unsafe {
+                                current_syscall_error._type = seL4_InvalidArgument;
+                                current_syscall_error.invalidArgumentNumber = 1;
+                            }
+                            return exception_t::EXCEPTION_SYSCALL_ERROR;
+                        }
+                    };
+                    if call {
+                        reply_words(get_currenct_thread(), &words);
+                        return exception_t::EXCEPTION_NONE;
+                    }
+                    set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
+                    return exception_t::EXCEPTION_NONE;
+                } else if label == MessageLabel::UintrAddAsyncSubmitter {
+                    // 第0个extra cap为通过该notification注册的共享ring所在的frame，
+                    // 当前线程以自己的身份加入该ring的提交线程，回复第0个消息寄存器为分配的提交者badge
+                    let ring_slot = get_extra_cap_by_index(0);
+                    if ring_slot.is_none() {
+                        debug!("UintrAddAsyncSubmitter: Truncated message.");
+                        unsafe { current_syscall_error._type = seL4_TruncatedMessage; }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    let ring_cap = ring_slot.unwrap().cap;
+                    if ring_cap.get_cap_type() != CapTag::CapFrameCap {
+                        debug!("UintrAddAsyncSubmitter: invalid frame cap.");
+                        unsafe {
+                            current_syscall_error._type = seL4_InvalidCapability;
+                            current_syscall_error.invalidCapNumber = 1;
+                        }
+                        return exception_t::EXCEPTION_SYSCALL_ERROR;
+                    }
+                    let badge = match crate::async_runtime::async_syscall_add_submitter(cap.get_nf_ptr(), ring_cap.get_frame_base_ptr(), get_currenct_thread()) {
+                        Some(badge) => badge,
+                        None => {
+                            debug!("UintrAddAsyncSubmitter: not a shared ring of the current address space.");
+                            unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+                            return exception_t::EXCEPTION_SYSCALL_ERROR;
+                        }
+                    };
+                    if call {
+                        reply_words(get_currenct_thread(), &[badge]);
+                        return exception_t::EXCEPTION_NONE;
                     }
                     set_thread_state(get_currenct_thread(), ThreadState::ThreadStateRestart);
                     return exception_t::EXCEPTION_NONE;
@@ -146,7 +339,24 @@ pub fn decode_invocation(label: MessageLabel, length: usize, slot: &mut cte_t, c
         CapTag::CapUntypedCap => decode_untyed_invocation(label, length, slot, cap, buffer),
         CapTag::CapIrqControlCap => decode_irq_control_invocation(label, length, slot, buffer),
         CapTag::CapIrqHandlerCap => decode_irq_handler_invocation(label, cap.get_irq_handler()),
+        #[cfg(feature = "ENABLE_UINTC")]
+        CapTag::CapUintrSenderCap => decode_uintr_invocation::decode_uintr_sender_invocation(label, cap, call),
+        #[cfg(feature = "ENABLE_UINTC")]
+        CapTag::CapUintrSenderTableCap => decode_uintr_invocation::decode_uintr_sender_table_invocation(label, cap),
         _ => decode_mmu_invocation(label, length, slot, call, buffer)
     }
 
-}
\ No newline at end of file
+}
+
+// 将words作为Call的回复写入消息寄存器。
+// 已经写好回复，置为Running以免handleInvocation用空回复覆盖
+#[cfg(feature = "ENABLE_UINTC")]
+fn reply_words(thread: &mut tcb_t, words: &[usize]) {
+    let mut length = 0;
+    for (i, word) in words.iter().enumerate() {
+        length = thread.set_mr(i, *word);
+    }
+    thread.set_register(badgeRegister, 0);
+    thread.set_register(msgInfoRegister, seL4_MessageInfo_t::new(0, 0, 0, length).to_word());
+    set_thread_state(thread, ThreadState::ThreadStateRunning);
+}
@@ -60,6 +60,13 @@ fn create_object(obj_type: ObjectType, region_base: pptr_t, user_size: usize, de
             cap_t::new_frame_cap(asidInvalid, region_base, obj_type.get_frame_type(),
                 VMReadWrite, device_mem, 0)
         }
+
+        #[cfg(feature = "ENABLE_UINTC")]
+        ObjectType::UintrSenderTableObject => {
+            // 表项全部无效
+            clear_memory(region_base as *mut u8, seL4_UintrSenderTableBits);
+            cap_t::new_uintr_sender_table_cap(region_base)
+        }
     }
 }
 
@@ -115,3 +122,31 @@ pub fn invoke_untyped_retype(src_slot: &mut cte_t, reset: bool, retype_base: ppt
         retype_base, user_size, device_mem);
     exception_t::EXCEPTION_NONE
 }
+
+// 批量retype中的一组对象
+pub struct RetypeBatchEntry {
+    pub new_type: ObjectType,
+    pub user_size: usize,
+    pub dest_cnode: pptr_t,
+    pub dest_offset: usize,
+    pub dest_length: usize,
+    pub retype_base: pptr_t,
+}
+
+// 在同一个untyped中依次创建多组对象，只重置一次untyped并更新一次free index，free_ref为最后一组对象之后的地址
+pub fn invoke_untyped_retype_batch(src_slot: &mut cte_t, reset: bool, entries: &[RetypeBatchEntry],
+                                   free_ref: pptr_t, device_mem: usize) -> exception_t {
+    let region_base = src_slot.cap.get_untyped_ptr();
+    if reset {
+        let status = reset_untyped_cap(src_slot);
+        if status != exception_t::EXCEPTION_NONE {
+            return status;
+        }
+    }
+    src_slot.cap.set_untyped_free_index(GET_FREE_INDEX(region_base, free_ref));
+    for entry in entries {
+        create_new_objects(entry.new_type, src_slot, convert_to_mut_type_ref::<cte_t>(entry.dest_cnode), entry.dest_offset,
+            entry.dest_length, entry.retype_base, entry.user_size, device_mem);
+    }
+    exception_t::EXCEPTION_NONE
+}
@@ -1,5 +1,5 @@
 pub mod decode;
-mod invoke_tcb;
+pub mod invoke_tcb;
 pub mod invoke_cnode;
 pub mod invoke_untyped;
 pub mod invoke_mmu_op;
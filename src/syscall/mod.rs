@@ -30,7 +30,7 @@ use crate::kernel::boot::{current_fault, current_lookup_fault};
 
 use self::invocation::handleInvocation;
 
-use crate::async_runtime::{coroutine_run_until_blocked, coroutine_wake, NEW_BUFFER_MAP, NewBuffer};
+use crate::async_runtime::{coroutine_run_until_blocked, AsyncWakeSource, coroutine_wake, coroutine_wake_on, async_syscall_can_submit, NEW_BUFFER_MAP, NewBuffer};
 use core::sync::atomic::Ordering::SeqCst;
 use crate::config::IRQConst::INTERRUPT_IPI_2;
 
@@ -139,6 +139,11 @@ fn send_fault_ipc(thread: &mut tcb_t) -> exception_t {
 
 #[inline]
 pub fn handle_fault(thread: &mut tcb_t) {
+    // 注册了fault ring的线程通过ring投递fault
+    #[cfg(feature = "ENABLE_UINTC")]
+    if crate::async_runtime::async_fault_deliver(thread) {
+        return;
+    }
     let fault = send_fault_ipc(thread);
     if fault != exception_t::EXCEPTION_NONE {
         debug!("send_fault_ipc fail: {:?}", fault);
@@ -156,6 +161,10 @@ fn handle_reply() {
         }
         let caller = convert_to_mut_type_ref::<tcb_t>(caller_cap.get_reply_tcb_ptr());
         current_thread.do_reply(caller, caller_slot, caller_cap.get_reply_can_grant() != 0);
+    } else {
+        // caller槽中没有reply cap时，回复可能属于一个异步Call
+        #[cfg(feature = "ENABLE_UINTC")]
+        crate::async_runtime::async_endpoint_reply(current_thread);
     }
 }
 
@@ -179,6 +188,8 @@ fn handle_recv(block: bool) {
                 return handle_fault(current_thread);
             }
             current_thread.delete_caller_cap();
+            #[cfg(feature = "ENABLE_UINTC")]
+            crate::async_runtime::async_endpoint_abort_reply(current_thread);
             convert_to_mut_type_ref::<endpoint_t>(ipc_cap.get_ep_ptr()).receive_ipc(
                 current_thread,
                 block,
@@ -216,15 +227,29 @@ fn handle_yield() {
 
 fn wake_syscall_handler() {
     // debug!("wake_syscall_handler: enter");
-    if let Some(cid) = get_currenct_thread().asyncSysHandlerCid {
-        // debug!("wake_syscall_handler: current thread's handler cid: {:?}", cid);
-        coroutine_wake(&cid);
-        if let Some(idle_cpu) = get_idle_cpu_index(get_currenct_thread().tcbPriority) {
-            // send ipi
-            let mask: usize = 1 << idle_cpu;
-            unsafe {
-                ipi_send_mask(INTERRUPT_IPI_2 as usize, mask, false);
-            }
+    // 唤醒当前线程可以提交请求的ring（自己注册的ring和所在地址空间的共享ring）中有请求的处理协程
+    let current = get_currenct_thread();
+    let idle_cpu = get_idle_cpu_index(current.tcbPriority);
+    let mut woken = false;
+    for map in unsafe { NEW_BUFFER_MAP.iter_mut() } {
+        if !async_syscall_can_submit(map, current) || !map.buf.has_request() {
+            continue;
+        }
+        woken = true;
+        map.stats.wakeups[AsyncWakeSource::Syscall as usize] += 1;
+        match idle_cpu {
+            // 将协程唤醒到空闲核的执行器上，再发送ipi让其执行
+            Some(idle_cpu) => coroutine_wake_on(&map.cid, idle_cpu),
+            None => coroutine_wake(&map.cid),
+        }
+    }
+    if let (true, Some(idle_cpu)) = (woken, idle_cpu) {
+        let mask: usize = 1 << idle_cpu;
+        unsafe {
+            ipi_send_mask(INTERRUPT_IPI_2 as usize, mask, false);
         }
     }
+    // 同时唤醒该线程注册的fault ring的处理协程
+    #[cfg(feature = "ENABLE_UINTC")]
+    crate::async_runtime::async_fault_ring_wake(get_currenct_thread());
 }
\ No newline at end of file
@@ -63,6 +63,8 @@ impl endpoint_t {
 
     #[inline]
     pub fn cancel_all_ipc(&mut self) {
+        #[cfg(feature = "ENABLE_UINTC")]
+        crate::async_runtime::async_endpoint_wake(self.get_ptr());
         match self.get_state() {
             EPState::Idle => {}
             _ => {
@@ -124,6 +126,8 @@ impl endpoint_t {
                     queue.ep_append(src_thread);
                     self.set_state(EPState::Send as usize);
                     self.set_queue(&queue);
+                    #[cfg(feature = "ENABLE_UINTC")]
+                    crate::async_runtime::async_endpoint_wake(self.get_ptr());
                 }
             }
 
@@ -166,6 +170,8 @@ impl endpoint_t {
                     queue.ep_append(thread);
                     self.set_state(EPState::Recv as usize);
                     self.set_queue(&queue);
+                    #[cfg(feature = "ENABLE_UINTC")]
+                    crate::async_runtime::async_endpoint_wake(self.get_ptr());
                 } else {
                     // NBReceive failed
                     thread.set_register(badgeRegister, 0);
@@ -28,6 +28,9 @@ impl tcb_t {
             }
 
             ThreadState::ThreadStateBlockedOnReply => {
+                // 由异步Recv收到的同步Call没有reply cap，使其reply token失效
+                #[cfg(feature = "ENABLE_UINTC")]
+                crate::async_runtime::async_endpoint_drop_caller(self);
                 self.tcbFault = seL4_Fault_t::new_null_fault();
                 let slot = self.get_cspace(tcbReply);
                 let caller_slot_ptr = slot.cteMDBNode.get_next();
@@ -541,6 +541,11 @@ fn idle_thread() {
     unsafe {
         loop {
             cpu_prio[cpu_id()] = 256;
+            #[cfg(feature = "ENABLE_UINTC")]
+            if crate::async_runtime::async_polling_on(cpu_id()) {
+                crate::async_runtime::async_polling_idle();
+                continue;
+            }
             asm!("wfi");
         }
     }
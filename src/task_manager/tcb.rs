@@ -1,10 +1,9 @@
 use core::intrinsics::{likely, unlikely};
-use crate::async_runtime::CoroutineId;
 
 use crate::MASK;
 use crate::common::fault::*;
 use crate::common::message_info::seL4_MessageInfo_t;
-use crate::common::utils::{pageBitsForSize, convert_to_mut_type_ref};
+use crate::common::utils::{pageBitsForSize, convert_to_mut_type_ref, convert_to_option_mut_type_ref};
 use crate::cspace::interface::{cte_t, resolve_address_bits, CapTag, cap_t, mdb_node_t, cte_insert};
 use crate::vspace::{set_vm_root, pptr_t, VMReadWrite, VMReadOnly};
 
@@ -59,7 +58,6 @@ pub struct tcb_t {
     pub tcbEPPrev: usize,
     #[cfg(feature = "ENABLE_UINTC")]
     pub uintr_inner: uintr_tcb_inner,
-    pub asyncSysHandlerCid: Option<CoroutineId>
 }
 
 #[cfg(feature = "ENABLE_UINTC")]
@@ -156,6 +154,11 @@ impl tcb_t {
 
     #[inline]
     pub fn unbind_notification(&mut self) {
+        // 用户态中断的接收者要求线程绑定notification，解绑时注销
+        #[cfg(feature = "ENABLE_UINTC")]
+        if let Some(ntfn) = convert_to_option_mut_type_ref::<crate::task_manager::ipc::notification_t>(self.tcbBoundNotification) {
+            crate::uintc::unregister_receiver(ntfn);
+        }
         self.tcbBoundNotification = 0;
     }
 
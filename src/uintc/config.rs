@@ -13,5 +13,12 @@ pub const UINTC_SIZE: usize = 0x4000;
 /// Maximum number of UINTC entries
 pub const UINTC_ENTRY_NUM: usize = 512;
 
+#[cfg(feature = "board_qemu")]
+/// UINTC entries that are never handed out to receivers
+pub const UINTC_RESERVED_ENTRIES: [usize; 4] = [1, 2, 3, 4];
+
+#[cfg(feature = "board_lrv")]
+pub const UINTC_RESERVED_ENTRIES: [usize; 4] = [1, 2, 3, 4];
+
 /// UINTC register width
 pub const UINTC_WIDTH: usize = 32;
\ No newline at end of file
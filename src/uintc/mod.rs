@@ -1,67 +1,157 @@
 pub(crate) mod config;
 mod operations;
 
+use alloc::vec::Vec;
 use bit_field::BitField;
 use lazy_static::lazy_static;
 use log::debug;
 use spin::Mutex;
 use crate::common::utils::{convert_to_mut_type_ref, convert_to_option_mut_type_ref, convert_to_type_ref, cpu_id};
-use crate::task_manager::{get_currenct_thread, tcb_t};
+use crate::task_manager::{get_currenct_thread, tcb_t, ThreadState};
 use crate::task_manager::ipc::notification_t;
-use crate::uintc::config::{UINTC_BASE, UINTC_ENTRY_NUM};
+use crate::uintc::config::{UINTC_BASE, UINTC_ENTRY_NUM, UINTC_RESERVED_ENTRIES};
 use crate::uintc::operations::{uintc_read_high, uintc_read_low, uintc_write_high, uintc_write_low};
 use crate::uintr;
 use crate::cspace::interface::{cap_t, CapTag};
+pub use async_runtime_core::IndexAllocator;
 use crate::uintr::{sip, suicfg, suirs, suist, uipi_read, uipi_send, uipi_write};
 use crate::vspace::{kpptr_to_paddr, pptr_to_paddr};
+use crate::common::{structures::exception_t, sel4_config::{seL4_IllegalOperation, seL4_DeleteFirst}};
+use crate::kernel::boot::current_syscall_error;
 
-#[derive(Copy, Clone)]
-pub struct IndexAllocator<const SIZE: usize> where
-    [(); (SIZE + 7) / 8]: {
-    bitmap: [u8; (SIZE + 7) / 8]
+lazy_static! {
+    // 接收者表项的编号即UINTC中的下标，板级配置中保留的表项不参与分配
+    pub static ref UINTR_RECV_ALLOCATOR: Mutex<IndexAllocator<UINTC_ENTRY_NUM>> = {
+        let mut allocator = IndexAllocator::<UINTC_ENTRY_NUM>::new();
+        for index in UINTC_RESERVED_ENTRIES {
+            allocator.reserve(index);
+        }
+        Mutex::new(allocator)
+    };
+    // 已绑定到线程的发送者表，内核发送者表始终存在
+    static ref UINTR_ST_TABLES: Mutex<Vec<UIntrSenderTable>> = {
+        let mut tables = Vec::new();
+        tables.push(UIntrSenderTable::new(kernel_sender_table(), 0));
+        Mutex::new(tables)
+    };
+    pub static ref NET_UINTR_IDX: Mutex<usize> = {
+        let idx = allocate_sender_entry(kernel_sender_table()).unwrap();
+        let entry = uist_entry(kernel_sender_table(), idx);
+        entry.set_valid(true);
+        entry.set_vec(0);
+        entry.set_index(0);
+        Mutex::new(idx)
+    };
 }
 
-impl<const SIZE: usize> IndexAllocator<SIZE> where
-    [(); (SIZE + 7) / 8]: {
-    pub fn new() -> Self {
-        Self {
-            bitmap :[0; (SIZE + 7) / 8]
-        }
+// 内核发送者表，用于异步系统调用、fault ring和网卡的用户态中断
+// 用户线程的发送者表为从untyped创建的UintrSenderTableObject，通过UintrBindSenderTable绑定到线程
+#[no_mangle]
+#[link_section = ".boot.uintr"]
+pub(crate) static mut UINTR_ST_POOL: [u8; core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM] = [0; core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM];
+
+// 发送者表及其表项的分配情况
+struct UIntrSenderTable {
+    // 发送者表的内核虚拟地址
+    ptr: usize,
+    // 绑定的线程，内核发送者表为0
+    tcb: usize,
+    entries: IndexAllocator<UINTC_ENTRY_NUM>,
+}
+
+impl UIntrSenderTable {
+    fn new(ptr: usize, tcb: usize) -> Self {
+        Self { ptr, tcb, entries: IndexAllocator::<UINTC_ENTRY_NUM>::new() }
     }
+}
+
+// 用户线程注册的发送者表项，用于注销和清理
+struct UIntrSender {
+    // 发送者表的内核虚拟地址
+    uist: usize,
+    // 表项在发送者表中的下标
+    offset: usize,
+    // 接收中断的notification
+    ntfn: usize,
+    // 通过发送权限（CapUintrSenderCap）注册时为其中断向量
+    cap_vec: Option<usize>,
+}
+
+static UINTR_SENDERS: Mutex<Vec<UIntrSender>> = Mutex::new(Vec::new());
+
+// 已注册接收者的notification
+static UINTR_RECEIVERS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+// 每个UINTC接收者表项中已经通过notification投递、但接收者尚未在UINTC中确认的向量，下标为接收者编号
+static UINTR_DELIVERED: Mutex<[u64; UINTC_ENTRY_NUM]> = Mutex::new([0; UINTC_ENTRY_NUM]);
+
+#[inline]
+fn uist_entry(uist: usize, offset: usize) -> &'static mut UIntrSTEntry {
+    convert_to_mut_type_ref::<UIntrSTEntry>(uist + offset * core::mem::size_of::<UIntrSTEntry>())
+}
 
-    pub fn allocate(&mut self) -> Option<usize> {
-        (0..SIZE).find(|i| {self.bitmap[i / 8] & (1 << (i % 8)) == 0 }).map(|index| {
-            self.bitmap[index / 8] |= 1 << (index % 8);
-            index
-        })
+#[inline]
+pub fn kernel_sender_table() -> usize {
+    unsafe { UINTR_ST_POOL.as_ptr() as usize }
+}
+
+fn allocate_sender_entry(uist: usize) -> Option<usize> {
+    UINTR_ST_TABLES.lock().iter_mut().find(|table| table.ptr == uist)?.entries.allocate()
+}
+
+fn release_sender_entry(uist: usize, offset: usize) {
+    uist_entry(uist, offset).set_valid(false);
+    if let Some(table) = UINTR_ST_TABLES.lock().iter_mut().find(|table| table.ptr == uist) {
+        table.entries.release(offset);
     }
+}
+
+#[inline]
+unsafe fn write_suist(paddr: usize) {
+    suist::write((1 << 63) | (DEFAULT_UIST_SIZE << 44) | (paddr >> 0xC));
+}
+
+// 切换到内核发送者表，内核发送用户态中断前调用
+#[inline]
+pub unsafe fn kernel_uist_activate() {
+    write_suist(kpptr_to_paddr(kernel_sender_table()));
+}
 
-    pub fn release(&mut self, index: usize) {
-        self.bitmap[index / 8] &= !(1 << (index % 8));
+// 将从untyped创建的发送者表绑定到线程，线程之后注册的发送者表项都位于该表中
+// 线程已有发送者表或该表已绑定到其他线程时返回DeleteFirst
+pub fn bind_sender_table(uist: usize, tcb: &mut tcb_t) -> exception_t {
+    let mut tables = UINTR_ST_TABLES.lock();
+    if tcb.uintr_inner.uist.is_some() || tables.iter().any(|table| table.ptr == uist) {
+        debug!("bind_sender_table: thread or table already bound");
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
+    tables.push(UIntrSenderTable::new(uist, tcb.get_ptr()));
+    tcb.uintr_inner.uist = Some(uist);
+    debug!("bind_sender_table: uist: {:#x}, tcb: {:#x}", uist, tcb.get_ptr());
+    exception_t::EXCEPTION_NONE
 }
 
-lazy_static! {
-    pub static ref UINTR_RECV_ALLOCATOR: Mutex<IndexAllocator<UINTC_ENTRY_NUM>> = Mutex::new(IndexAllocator::<UINTC_ENTRY_NUM>::new());
-    pub static ref UINTR_ST_POOL_ALLOCATOR: Mutex<IndexAllocator<16>> = Mutex::new(IndexAllocator::<16>::new());
-    pub static ref UINTR_ST_ENTRY_ALLOCATOR: Mutex<[IndexAllocator<UINTC_ENTRY_NUM>; 16]> = Mutex::new([IndexAllocator::<UINTC_ENTRY_NUM>::new(); 16]);
-    pub static ref KERNEL_SENDER_POOL_IDX: Mutex<usize> = Mutex::new(UINTR_ST_POOL_ALLOCATOR.lock().allocate().unwrap());
-    pub static ref NET_UINTR_IDX: Mutex<usize> = unsafe {
-        let uist_idx = *KERNEL_SENDER_POOL_IDX.lock();
-        let idx = UINTR_ST_ENTRY_ALLOCATOR.lock().get_mut(uist_idx).unwrap().allocate().unwrap();
-        let entry = convert_to_mut_type_ref::<UIntrSTEntry>(UINTR_ST_POOL.as_ptr().offset(((
-            uist_idx * UINTC_ENTRY_NUM + idx) * core::mem::size_of::<UIntrSTEntry>()) as isize) as usize);
-        entry.set_valid(true);
-        entry.set_vec(0);
-        entry.set_index(0);
-        Mutex::new(idx)
+// 发送者表对象的最后一个权限被删除时调用，释放表中的所有表项并与线程解绑
+// 绑定的线程可能正在其他核上运行，其suist仍指向该表，需要让该核重新进入内核，返回用户态时按解绑后的状态清零suist
+pub fn release_sender_table_object(uist: usize) {
+    release_senders_by(|sender| sender.uist == uist);
+    let table = {
+        let mut tables = UINTR_ST_TABLES.lock();
+        match tables.iter().position(|table| table.ptr == uist) {
+            Some(pos) => tables.remove(pos),
+            None => return,
+        }
     };
+    if let Some(tcb) = convert_to_option_mut_type_ref::<tcb_t>(table.tcb) {
+        tcb.uintr_inner.uist = None;
+        #[cfg(feature = "ENABLE_SMP")]
+        unsafe {
+            crate::deps::remoteTCBStall(tcb);
+        }
+    }
 }
 
-#[no_mangle]
-#[link_section = ".boot.uintr"]
-pub(crate) static mut UINTR_ST_POOL: [u8; core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM * 16] = [0; core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM * 16];
-
 #[derive(Debug)]
 pub struct UIntrSTEntry(u64);
 const DEFAULT_UIST_SIZE: usize = 1;
@@ -143,76 +233,232 @@ impl UIntrReceiver {
     }
 }
 
-static ALLOCATE_ID_TO_RS_ID: [usize; 8] = [0, 5, 6, 7, 8, 9, 10, 11];
-
-pub fn register_receiver(ntfn: &mut notification_t, tcb: &mut tcb_t) {
+// 线程需要先绑定该notification，否则返回seL4_IllegalOperation；notification已经注册过接收者
+// 或UINTC中没有空闲的接收者表项时返回seL4_DeleteFirst
+pub fn register_receiver(ntfn: &mut notification_t, tcb: &mut tcb_t) -> exception_t {
     if tcb.tcbBoundNotification != ntfn.get_ptr() {
         debug!("fail to register uint receiver, need to bind ntfn first");
-        return;
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
     }
-    if let Some(mut recv_index) = UINTR_RECV_ALLOCATOR.lock().allocate() {
-        recv_index = ALLOCATE_ID_TO_RS_ID[recv_index];
-        debug!("recv index: {}", recv_index);
-        ntfn.set_uintr_flag(1);
-        ntfn.set_recv_idx(recv_index);
-        let mut uirs = UIntrReceiver::from(recv_index);
-        uirs.irq = 0;
-        uirs.sync(recv_index);
-        tcb.uintr_inner.utvec = uintr::utvec::read().bits();
-        tcb.uintr_inner.uscratch = uintr::uscratch::read();
-    } else {
-        debug!("register_receiver fail");
+    // 重复注册会分配新的表项，旧表项既不会被清空也不会被回收
+    if ntfn.get_uintr_flag() == 1 {
+        debug!("register_receiver: ntfn already registered, recv index: {}", ntfn.get_recv_idx());
+        unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    let recv_index = match UINTR_RECV_ALLOCATOR.lock().allocate() {
+        Some(recv_index) => recv_index,
+        None => {
+            debug!("register_receiver: no free receiver entry in UINTC");
+            unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+            return exception_t::EXCEPTION_SYSCALL_ERROR;
+        }
+    };
+    debug!("recv index: {}", recv_index);
+    ntfn.set_uintr_flag(1);
+    ntfn.set_recv_idx(recv_index);
+    let mut receivers = UINTR_RECEIVERS.lock();
+    if !receivers.contains(&ntfn.get_ptr()) {
+        receivers.push(ntfn.get_ptr());
     }
+    UINTR_DELIVERED.lock()[recv_index] = 0;
+    let mut uirs = UIntrReceiver::from(recv_index);
+    uirs.irq = 0;
+    uirs.sync(recv_index);
+    tcb.uintr_inner.utvec = uintr::utvec::read().bits();
+    tcb.uintr_inner.uscratch = uintr::uscratch::read();
+    exception_t::EXCEPTION_NONE
 }
 
-pub fn register_sender(ntfn_cap: &cap_t) {
+// 通过notification在当前线程的发送者表中注册表项，表项下标写入IPC buffer的uintrFlag
+// 早期版本在线程没有发送者表时由内核分配，现在线程需要先从untyped创建发送者表对象并通过UintrBindSenderTable绑定，
+// 没有绑定时返回IllegalOperation而不是静默成功；发送者表已满时返回DeleteFirst
+pub fn register_sender(ntfn_cap: &cap_t) -> exception_t {
     assert_eq!(ntfn_cap.get_cap_type(), CapTag::CapNotificationCap);
-    let current = get_currenct_thread();
-    if current.uintr_inner.uist.is_none() {
-        if let Some(uist_idx) = UINTR_ST_POOL_ALLOCATOR.lock().allocate() {
-            current.uintr_inner.uist = Some(uist_idx);
-        } else {
-            debug!("alloc sender table fail");
-            return;
+    if get_currenct_thread().uintr_inner.uist.is_none() {
+        debug!("register_sender: no sender table bound, bind one with UintrBindSenderTable first");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return exception_t::EXCEPTION_SYSCALL_ERROR;
+    }
+    match install_sender(ntfn_cap.get_nf_ptr(), ntfn_cap.get_nf_badge(), None) {
+        Some(offset) => {
+            let ipc_buffer = get_currenct_thread().lookup_mut_ipc_buffer(true).unwrap();
+            ipc_buffer.uintrFlag = offset;
+            debug!("[register_sender] offset: {}", offset);
+            exception_t::EXCEPTION_NONE
+        }
+        None => {
+            unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+            exception_t::EXCEPTION_SYSCALL_ERROR
         }
     }
-    let uist_idx = current.uintr_inner.uist.unwrap();
-    let uiste_idx = UINTR_ST_ENTRY_ALLOCATOR.lock().get_mut(uist_idx).unwrap().allocate();
+}
+
+// 通过发送权限在当前线程的发送者表中注册表项，返回表项下标
+// notification没有注册接收者或线程没有绑定发送者表时返回IllegalOperation，发送者表已满时返回DeleteFirst
+pub fn register_sender_cap(sender_cap: &cap_t) -> Result<usize, exception_t> {
+    assert_eq!(sender_cap.get_cap_type(), CapTag::CapUintrSenderCap);
+    let ntfn = sender_cap.get_uintr_sender_ntfn_ptr();
+    if convert_to_type_ref::<notification_t>(ntfn).get_uintr_flag() != 1 || get_currenct_thread().uintr_inner.uist.is_none() {
+        debug!("register_sender_cap: notification has no receiver or no sender table bound");
+        unsafe { current_syscall_error._type = seL4_IllegalOperation; }
+        return Err(exception_t::EXCEPTION_SYSCALL_ERROR);
+    }
+    let vec = sender_cap.get_uintr_sender_vec();
+    match install_sender(ntfn, vec, Some(vec)) {
+        Some(offset) => {
+            let ipc_buffer = get_currenct_thread().lookup_mut_ipc_buffer(true).unwrap();
+            ipc_buffer.uintrFlag = offset;
+            debug!("register_sender_cap: offset: {}, vec: {}", offset, vec);
+            Ok(offset)
+        }
+        None => {
+            unsafe { current_syscall_error._type = seL4_DeleteFirst; }
+            Err(exception_t::EXCEPTION_SYSCALL_ERROR)
+        }
+    }
+}
+
+// 在当前线程绑定的发送者表中填写指向ntfn接收者的表项，没有绑定发送者表或表已满时返回None
+fn install_sender(ntfn: usize, vec: usize, cap_vec: Option<usize>) -> Option<usize> {
+    let uist = match get_currenct_thread().uintr_inner.uist {
+        Some(uist) => uist,
+        None => {
+            debug!("no sender table bound");
+            return None;
+        }
+    };
+    let uiste_idx = allocate_sender_entry(uist);
     if uiste_idx.is_none() {
-        debug!("fail to alloc uiste. {}", uist_idx);
-        return;
+        debug!("fail to alloc uiste. {:#x}", uist);
+        return None;
     }
     let offset = uiste_idx.unwrap();
-    let entry = unsafe {
-        debug!("UINTR_ST_POOL.as_ptr(): {:#x}", UINTR_ST_POOL.as_ptr() as usize);
-        convert_to_mut_type_ref::<UIntrSTEntry>(UINTR_ST_POOL.as_ptr().offset(((uist_idx * UINTC_ENTRY_NUM + offset) * core::mem::size_of::<UIntrSTEntry>()) as isize) as usize)
-    };
+    let entry = uist_entry(uist, offset);
     debug!("entry.as_ptr(): {:#x}", entry as *const UIntrSTEntry as usize);
     entry.set_valid(true);
-    entry.set_vec(ntfn_cap.get_nf_badge());
-    debug!("[register sender] recv_idx: {}", convert_to_type_ref::<notification_t>(ntfn_cap.get_nf_ptr()).get_recv_idx());
-    entry.set_index(convert_to_type_ref::<notification_t>(ntfn_cap.get_nf_ptr()).get_recv_idx());
+    entry.set_vec(vec);
+    debug!("[register sender] recv_idx: {}", convert_to_type_ref::<notification_t>(ntfn).get_recv_idx());
+    entry.set_index(convert_to_type_ref::<notification_t>(ntfn).get_recv_idx());
     debug!("entry: {:?}", entry);
-    // debug!("{} {} {} {} {}", entry.get_send_vec(), entry.get_uirs_index(), entry.get_valid(), entry.get_reserved0(), entry.get_reserved1());
-    let ipc_buffer = current.lookup_mut_ipc_buffer(true).unwrap();
-    ipc_buffer.uintrFlag = offset;
-    debug!("[register_sender] offset: {}", offset);
+    UINTR_SENDERS.lock().push(UIntrSender { uist, offset, ntfn, cap_vec });
+    Some(offset)
+}
+
+// 最后一个发送权限被删除时调用，释放所有线程中通过该权限注册的表项
+pub fn release_sender_cap(ntfn: usize, vec: usize) {
+    let count = release_senders_by(|sender| sender.ntfn == ntfn && sender.cap_vec == Some(vec));
+    debug!("release_sender_cap: ntfn: {:#x}, vec: {}, released: {}", ntfn, vec, count);
+}
+
+// 无效化并释放满足条件的用户发送者表项，返回释放的个数
+fn release_senders_by<F: Fn(&UIntrSender) -> bool>(f: F) -> usize {
+    let mut senders = UINTR_SENDERS.lock();
+    let count = senders.len();
+    senders.retain(|sender| {
+        if !f(sender) {
+            return true;
+        }
+        release_sender_entry(sender.uist, sender.offset);
+        debug!("release sender: uist: {:#x}, offset: {}", sender.uist, sender.offset);
+        false
+    });
+    count - senders.len()
+}
+
+// 注销线程发送者表中以ntfn为目标的表项，没有这样的表项时返回false
+// cap_vec为None时注销通过notification注册的表项，否则注销通过对应向量的发送权限注册的表项
+pub fn unregister_sender(tcb: &tcb_t, ntfn: usize, cap_vec: Option<usize>) -> bool {
+    match tcb.uintr_inner.uist {
+        Some(uist) => release_senders_by(|sender| sender.uist == uist && sender.ntfn == ntfn && sender.cap_vec == cap_vec) != 0,
+        None => false,
+    }
+}
+
+// 线程被删除时调用，释放其发送者表中的表项并解绑，发送者表对象可以再绑定到其他线程
+pub fn release_sender_table(tcb: &mut tcb_t) {
+    if let Some(uist) = tcb.uintr_inner.uist.take() {
+        release_senders_by(|sender| sender.uist == uist);
+        UINTR_ST_TABLES.lock().retain(|table| table.ptr != uist);
+    }
+}
+
+// 注销notification的接收者：以该notification为目标的发送者表项（包括异步系统调用和fault ring的内核发送者）被释放，ring本身保留，
+// UINTC表项被清空并回收，uintr_flag和recv_idx被清零。notification没有注册接收者时只释放发送者表项，返回false
+// 在注销调用、线程与notification解绑和notification被删除时调用
+pub fn unregister_receiver(ntfn: &mut notification_t) -> bool {
+    release_senders_by(|sender| sender.ntfn == ntfn.get_ptr());
+    crate::async_runtime::async_syscall_detach_ntfn(ntfn.get_ptr());
+    if ntfn.get_uintr_flag() != 1 {
+        return false;
+    }
+    let recv_index = ntfn.get_recv_idx();
+    let mut uirs = UIntrReceiver::from(recv_index);
+    uirs.mode = 0;
+    uirs.irq = 0;
+    uirs.sync(recv_index);
+    UINTR_RECV_ALLOCATOR.lock().release(recv_index);
+    UINTR_DELIVERED.lock()[recv_index] = 0;
+    UINTR_RECEIVERS.lock().retain(|ptr| *ptr != ntfn.get_ptr());
+    ntfn.set_uintr_flag(0);
+    ntfn.set_recv_idx(0);
+    debug!("unregister_receiver: recv index: {}", recv_index);
+    true
+}
+
+// 接收者没有运行时UINTC中挂起的用户态中断要等到其下次返回用户态才会被处理。
+// 对阻塞在Recv或其绑定的notification上的接收者，将挂起的向量作为badge通过绑定的notification发送并唤醒接收者。
+// UINTC没有写1清零或原子确认操作，内核读-改-写high寄存器会覆盖其他核同时通过SEND置位的向量，因此内核从不清除UINTC：
+// 挂起的向量由接收者返回用户态后在自己的用户态中断处理中确认，内核只在UINTR_DELIVERED中记录已投递的向量，
+// 同一向量在被确认之前不会重复投递。接收者可能既从notification的badge、又从用户态中断看到同一向量，处理需幂等。
+// 只在核0的时钟中断中调用，接收者被唤醒的延迟最多为一个时钟周期
+pub fn uintr_deliver_pending() {
+    let receivers = UINTR_RECEIVERS.lock().clone();
+    for ntfn_ptr in receivers {
+        let ntfn = convert_to_mut_type_ref::<notification_t>(ntfn_ptr);
+        if ntfn.get_uintr_flag() != 1 {
+            continue;
+        }
+        let tcb = match convert_to_option_mut_type_ref::<tcb_t>(ntfn.get_bound_tcb()) {
+            Some(tcb) => tcb,
+            None => continue,
+        };
+        let blocked = match tcb.get_state() {
+            ThreadState::ThreadStateBlockedOnReceive => true,
+            ThreadState::ThreadStateBlockedOnNotification => tcb.tcbState.get_blocking_object() == ntfn_ptr,
+            _ => false,
+        };
+        if !blocked {
+            continue;
+        }
+        let index = ntfn.get_recv_idx();
+        let pending = uintc_read_high(index);
+        let mut delivered = UINTR_DELIVERED.lock();
+        // 接收者已确认的向量不再记录，之后再次到达时重新投递
+        delivered[index] &= pending;
+        let new = pending & !delivered[index];
+        if new == 0 {
+            continue;
+        }
+        delivered[index] |= new;
+        drop(delivered);
+        debug!("uintr_deliver_pending: recv index: {}, pending: {:#x}", index, new);
+        ntfn.send_signal(new as usize);
+    }
 }
 
 pub fn register_sender_async_syscall(ntfn_cap: &cap_t) -> isize {
     assert_eq!(ntfn_cap.get_cap_type(), CapTag::CapNotificationCap);
-    let uist_idx = *KERNEL_SENDER_POOL_IDX.lock();
-    debug!("register sender async syscall: uist_idx: {:#x}", uist_idx);
-    let uiste_idx = UINTR_ST_ENTRY_ALLOCATOR.lock().get_mut(uist_idx).unwrap().allocate();
+    let uist = kernel_sender_table();
+    debug!("register sender async syscall: uist: {:#x}", uist);
+    let uiste_idx = allocate_sender_entry(uist);
     if uiste_idx.is_none() {
-        debug!("register sender async syscall: fail to alloc uiste. {}", uist_idx);
+        debug!("register sender async syscall: fail to alloc uiste. {:#x}", uist);
         return -1;
     }
     let offset = uiste_idx.unwrap();
-    let entry = unsafe {
-        debug!("register sender async syscall: UINTR_ST_POOL.as_ptr(): {:#x}", UINTR_ST_POOL.as_ptr() as usize);
-        convert_to_mut_type_ref::<UIntrSTEntry>(UINTR_ST_POOL.as_ptr().offset(((uist_idx * UINTC_ENTRY_NUM + offset) * core::mem::size_of::<UIntrSTEntry>()) as isize) as usize)
-    };
+    let entry = uist_entry(uist, offset);
     debug!("register sender async syscall: entry.as_ptr(): {:#x}", entry as *const UIntrSTEntry as usize);
     entry.set_valid(true);
     entry.set_vec(0);
@@ -224,6 +470,11 @@ pub fn register_sender_async_syscall(ntfn_cap: &cap_t) -> isize {
     return offset as isize;
 }
 
+pub fn unregister_sender_async_syscall(offset: usize) {
+    let uist = kernel_sender_table();
+    release_sender_entry(uist, offset);
+    debug!("unregister sender async syscall: offset: {}", offset);
+}
 
 pub fn init() {
     debug!("UINTC_BASE: {:#x}", UINTC_BASE);
@@ -290,10 +541,9 @@ unsafe fn uirs_restore() {
 }
 
 unsafe fn uist_init() {
-    if let Some(uist_idx) = get_currenct_thread().uintr_inner.uist {
-        let frame_addr = UINTR_ST_POOL.as_ptr().offset((uist_idx * core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM) as isize) as usize;
-        // debug!("frame_addr: {:#x}", frame_addr);
-        suist::write((1 << 63) | (1 << 44) | (kpptr_to_paddr(frame_addr) >> 0xC));
+    if let Some(uist) = get_currenct_thread().uintr_inner.uist {
+        // 用户线程的发送者表从untyped创建
+        write_suist(pptr_to_paddr(uist));
     } else {
         suist::write(0);
     }
@@ -307,7 +557,7 @@ pub unsafe fn test_uintr(hartid: usize) {
     debug!("test uintr start, hartid: {}", hartid);
 
     // Enable receiver status.
-    let uirs_index = ALLOCATE_ID_TO_RS_ID[hartid];
+    let uirs_index = UINTR_RECV_ALLOCATOR.lock().allocate().unwrap();
     // Receiver on hart hartid
     *((UINTC_BASE + uirs_index * 0x20 + 8) as *mut u64) = ((hartid << 16) as u64) | 3;
 
@@ -318,15 +568,10 @@ pub unsafe fn test_uintr(hartid: usize) {
     assert_eq!(uipi_read(), 0x00010000);
 
     // Enable sender status.
-    let uist_idx = 0;
-    let frame = UINTR_ST_POOL.as_ptr().offset((uist_idx * core::mem::size_of::<UIntrSTEntry>() * UINTC_ENTRY_NUM) as isize) as usize;
-    suist::write((1 << 63) | (1 << 44) | (kpptr_to_paddr(frame) >> 0xC));
-
-    let offset = 0;
-    let entry = unsafe {
-        // debug!("UINTR_ST_POOL.as_ptr(): {:#x}", UINTR_ST_POOL.as_ptr() as usize);
-        convert_to_mut_type_ref::<UIntrSTEntry>(UINTR_ST_POOL.as_ptr().offset(((uist_idx * UINTC_ENTRY_NUM + offset) * core::mem::size_of::<UIntrSTEntry>()) as isize) as usize)
-    };
+    kernel_uist_activate();
+
+    let offset = allocate_sender_entry(kernel_sender_table()).unwrap();
+    let entry = uist_entry(kernel_sender_table(), offset);
     // debug!("entry.as_ptr(): {:#x}", entry as *const UIntrSTEntry as usize);
     entry.set_valid(true);
     entry.set_vec(hartid);
@@ -334,7 +579,7 @@ pub unsafe fn test_uintr(hartid: usize) {
     entry.set_index(uirs_index);
 
     log::info!("Send UIPI!");
-    uipi_send(0);
+    uipi_send(offset);
 
     loop {
         if sip::read().usoft() {
@@ -344,4 +589,6 @@ pub unsafe fn test_uintr(hartid: usize) {
             break;
         }
     }
+    release_sender_entry(kernel_sender_table(), offset);
+    UINTR_RECV_ALLOCATOR.lock().release(uirs_index);
 }
\ No newline at end of file